@@ -30,6 +30,7 @@ use crate::{color_settings::*, grid_config::*, input::*, ui::*, ui_control::*, u
 use crate::cell::*;
 use crate::cell_grid::*;
 use crate::grid_display::*;
+use crate::grid_chunks::CHUNK_SIZE;
 
 use enum_map::{enum_map, EnumMap};
 
@@ -56,15 +57,19 @@ pub fn run_sandshrew_app() {
         .add_systems(Update, (
             set_window_icon,
             update_input,
-            start_stop_button_interactions,
-            speed_button_interactions,
-            cell_type_button_interactions,
-            brush_type_button_interactions,
-            color_pallete_button_interactions,
-            save_button_interactions,
-            load_button_interactions,
-            replace_solids_button_interactions,
-            top_gass_leak_button_interactions,
+            (
+                start_stop_button_interactions,
+                speed_button_interactions,
+                cell_type_button_interactions,
+                brush_type_button_interactions,
+                color_pallete_button_interactions,
+                save_button_interactions,
+                load_button_interactions,
+                replace_solids_button_interactions,
+                boundary_button_interactions,
+                toggle_button_interactions,
+                grid_size_button_interactions,
+            ).chain(),
             brush_size_mouse_scroll,
             brush_size_slider_interactions,
             update_fps,
@@ -79,8 +84,10 @@ pub struct GameGlobals
 {
     pub render_image: AssetId<Image>,
     pub buttons_config: Vec<CellTypeButtonConfig>,
-    pub img_size: u32,
-    pub out_tex_size: u32,
+    // screen pixels per cell
+    pub pixel_scale: u32,
+    // bottom left corner of the visible part of the grid, in cells
+    pub view_pos: Vec2,
     pub frame_num: usize,
     pub brush_type: BrushType,
     pub brush_size: i32,
@@ -90,8 +97,8 @@ pub struct GameGlobals
     pub prev_mouse_press: Option<MouseButton>,
     pub left_pressed_on_canvas: bool,
     pub right_pressed_on_canvas: bool,
+    pub middle_pressed_on_canvas: bool,
     pub replace_solids_button_pressed: bool,
-    pub top_gass_leak_button_pressed: bool,
     pub save_button_pressed: bool,
     pub load_button_pressed: bool,
     pub grid: CellGrid,
@@ -100,10 +107,46 @@ pub struct GameGlobals
     pub curr_color_setting: usize,
     pub place_cell_type: CellType,
     pub replace_solids: bool,
+    // show the cell temperatures instead of the materials
+    pub heat_map: bool,
     pub paused: bool,
     pub speed: UpdateSpeed,
 }
 
+// largest drawing canvas in screen pixels, bigger grids are only shown in part
+pub const MAX_CANVAS_SIZE: IVec2 = IVec2::splat(800);
+// chunks this many cells around the view are simulated as well, the rest of the grid is frozen
+pub const SIMULATION_MARGIN: i32 = 4 * CHUNK_SIZE;
+
+impl GameGlobals {
+    pub fn view(&self) -> GridView {
+        let grid_sizes = self.grid.cells.sizes;
+        let sizes = (MAX_CANVAS_SIZE / self.pixel_scale as i32).min(grid_sizes);
+        let pos = self.view_pos.round().as_ivec2().clamp(IVec2::ZERO, grid_sizes - sizes);
+        GridView { pos, sizes }
+    }
+
+    // size of the drawing canvas in screen pixels
+    pub fn canvas_size(&self) -> Vec2 {
+        (self.view().sizes * self.pixel_scale as i32).as_vec2()
+    }
+
+    // moves the view by the number of cells, but not past the grid edges
+    pub fn pan_view(&mut self, delta: Vec2) {
+        let max_pos = (self.grid.cells.sizes - self.view().sizes).as_vec2();
+        self.view_pos = (self.view_pos + delta).clamp(Vec2::ZERO, max_pos);
+    }
+
+    // changes the pixel scale, the cell in the middle of the view stays there
+    pub fn zoom_view(&mut self, step: i32) {
+        let view = self.view();
+        let center = view.pos.as_vec2() + view.sizes.as_vec2() * 0.5;
+        self.pixel_scale = self.pixel_scale.saturating_add_signed(step).clamp(1, MAX_PIXEL_SCALE);
+        self.view_pos = center - self.view().sizes.as_vec2() * 0.5;
+        self.pan_view(Vec2::ZERO);
+    }
+}
+
 #[derive(Component)]
 pub struct FpsDisplayTimer {
     pub timer: Timer,
@@ -126,13 +169,15 @@ fn setup(
 
     commands.insert_resource(IconImage {handle: icon_handle});
 
-    let img_size = 200u32;
+    let grid_sizes = IVec2::splat(200);
     #[cfg(debug_assertions)]
-    let img_size = 128u32;
+    let grid_sizes = IVec2::splat(128);
+    let pixel_scale = 4u32;
 
-    let img_data = vec![255u8; (img_size*img_size*4) as usize];
+    let img_size = grid_sizes.as_uvec2();
+    let img_data = vec![255u8; (img_size.x*img_size.y*4) as usize];
     let mut img = Image::new(
-        Extent3d { width: img_size, height: img_size, depth_or_array_layers: 1 }, 
+        Extent3d { width: img_size.x, height: img_size.y, depth_or_array_layers: 1 }, 
         TextureDimension::D2,
         img_data,
         render_resource::TextureFormat::Rgba8Unorm,
@@ -149,21 +194,22 @@ fn setup(
     // camera
     commands.spawn(Camera2dBundle::default());
 
-    let out_tex_size = 4*img_size;
-
     commands.spawn(FpsDisplayTimer { timer: Timer::from_seconds(
         0.25,
         TimerMode::Repeating,
     )});
     
-    let grid = get_default_cell_grid(img_size);
+    let seed = rand::random::<u64>();
+    info!("simulation seed: {}", seed);
+    let grid = load_cell_grid(grid_sizes, seed);
 
-    let buttons_config = get_cell_type_buttons_config();           
+    let buttons_config = get_cell_type_buttons_config(&grid.materials);           
 
     let display = GridDisplay {
         //shallow_water_color: Color::rgb_u8(27, 52, 135),
         shallow_water_color: Srgba::from_u8_array_no_alpha([31, 61, 157]).into(),
         brush_edge_color: Srgba::new(1.0, 1.0, 1.0, 0.1).into(),
+        charge_color: Srgba::from_u8_array_no_alpha([255, 238, 131]).into(),
     };
 
     let color_settings = vec![lospec500_palette(), cc_29_palette(), resurrect64_palette()];
@@ -171,8 +217,8 @@ fn setup(
     let globals = GameGlobals {
         render_image: img_handle.id(),
         buttons_config,
-        img_size,
-        out_tex_size,
+        pixel_scale,
+        view_pos: Vec2::ZERO,
         frame_num: 0,
         brush_type: BrushType::Circle,
         brush_size: 7,
@@ -182,8 +228,8 @@ fn setup(
         prev_mouse_press: None,
         left_pressed_on_canvas: false,
         right_pressed_on_canvas: false,
+        middle_pressed_on_canvas: false,
         replace_solids_button_pressed: false,
-        top_gass_leak_button_pressed: false,
         save_button_pressed: false,
         load_button_pressed: false,
         grid,
@@ -192,11 +238,12 @@ fn setup(
         curr_color_setting: 0,
         place_cell_type: CellType::Sand,
         replace_solids: false,
+        heat_map: false,
         paused: false,
         speed: UpdateSpeed::Normal,
     };
     
-    setup_ui(&mut commands, &asset_server, out_tex_size, img_handle, &mut images, &globals);
+    setup_ui(&mut commands, &asset_server, img_handle, &mut images, &globals);
 
     commands.spawn(globals);
 
@@ -230,6 +277,29 @@ fn set_window_icon(
 fn set_window_icon() {
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+fn load_cell_grid(grid_sizes: IVec2, seed: u64) -> CellGrid
+{
+    let path = bevy::asset::io::file::FileAssetReader::get_base_path().join("assets").join(GRID_CONFIG_FILE);
+    match load_grid_config(&path).and_then(|config| cell_grid_from_config(&config, grid_sizes, seed)) {
+        Ok(grid) => {
+            info!("grid config loaded from {}", path.display());
+            grid
+        },
+        Err(err) => {
+            warn!("using the default grid config, failed to load {}: {}", path.display(), err);
+            get_default_cell_grid(grid_sizes, seed)
+        },
+    }
+}
+
+// no file system access, the compiled in defaults are used
+#[cfg(target_arch = "wasm32")]
+fn load_cell_grid(grid_sizes: IVec2, seed: u64) -> CellGrid
+{
+    get_default_cell_grid(grid_sizes, seed)
+}
+
 fn update_cells(mut globals_query: Query<&mut GameGlobals>)
 {
     let mut globals = globals_query.single_mut();
@@ -237,6 +307,9 @@ fn update_cells(mut globals_query: Query<&mut GameGlobals>)
         return;
     }
 
+    let view = globals.view();
+    globals.grid.chunks.set_active_rect(view.pos - SIMULATION_MARGIN, view.pos + view.sizes - 1 + SIMULATION_MARGIN);
+
     let speed = globals.speed;
     let frame_num = globals.frame_num;
     let mut call_update = |update_num: usize| {
@@ -264,6 +337,7 @@ fn update_cells(mut globals_query: Query<&mut GameGlobals>)
 fn draw_to_out_img(mut images: ResMut<Assets<Image>>,
     mut globals_query: Query<&mut GameGlobals>,
     relative_cursor_position_query: Query<&RelativeCursorPosition, With<DrawingCanvas>>,
+    mut canvas_query: Query<&mut Node, With<DrawingCanvas>>,
     mouse_button: Res<ButtonInput<MouseButton>>,
     ) {
     //let start = Instant::now();
@@ -272,12 +346,31 @@ fn draw_to_out_img(mut images: ResMut<Assets<Image>>,
     let relative_cursor_position = relative_cursor_position_query.single();
     let mouse_over = relative_cursor_position.mouse_over();
 
-    globals.display.display(&globals.grid.cells, &globals.grid.cell_properties, image);
+    // only the visible part of the grid is rendered, the image and the canvas follow its size
+    let view = globals.view();
+    if image.size().as_ivec2() != view.sizes {
+        image.resize(Extent3d { width: view.sizes.x as u32, height: view.sizes.y as u32, depth_or_array_layers: 1 });
+        image.data.fill(255);
+    }
+    let canvas_size = globals.canvas_size();
+    let mut canvas = canvas_query.single_mut();
+    if canvas.width != Val::Px(canvas_size.x) || canvas.height != Val::Px(canvas_size.y) {
+        canvas.width = Val::Px(canvas_size.x);
+        canvas.height = Val::Px(canvas_size.y);
+    }
+
+    if globals.heat_map {
+        globals.display.display_heat_map(&globals.grid.cells, &view, image);
+        globals.display.display_particles_heat_map(&globals.grid.particles, &globals.grid.cells, &view, image);
+    } else {
+        globals.display.display(&globals.grid.cells, &globals.grid.materials, &view, image);
+        globals.display.display_particles(&globals.grid.particles, &globals.grid.cells, &globals.grid.materials, &view, image);
+    }
     let prev_cursor_pos = globals.prev_cursor_pos;
     let maybe_cursor_pos = get_out_img_cursor_pos(relative_cursor_position, &globals);
     if let Some(cursor_pos) = maybe_cursor_pos {
         if mouse_over || globals.left_pressed_on_canvas || globals.right_pressed_on_canvas {
-            globals.display.draw_brush_edge(&globals.grid.cells, image, cursor_pos, prev_cursor_pos, globals.brush_type, globals.brush_size);
+            globals.display.draw_brush_edge(&view, image, cursor_pos, prev_cursor_pos, globals.brush_type, globals.brush_size);
         }
     }
 
@@ -0,0 +1,209 @@
+use bevy_math::prelude::*;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::cell::*;
+use crate::cell_grid::*;
+use crate::grid_chunks::*;
+use crate::materials::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Edge
+{
+    Left,
+    Right,
+    Bottom,
+    Top,
+}
+
+impl Edge {
+    pub const ALL: [Edge; 4] = [Edge::Left, Edge::Right, Edge::Bottom, Edge::Top];
+
+    pub fn opposite(self) -> Edge {
+        match self {
+            Edge::Left => Edge::Right,
+            Edge::Right => Edge::Left,
+            Edge::Bottom => Edge::Top,
+            Edge::Top => Edge::Bottom,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BoundaryType
+{
+    // nothing gets through
+    Wall,
+    // everything leaving the grid is deleted
+    Void,
+    // cells leaving the grid come back from the opposite edge, which has to wrap too
+    Wrap,
+    // wall that keeps filling the free cells along it with a material
+    Inflow,
+}
+
+impl BoundaryType {
+    // order in which the settings button cycles through the types
+    pub fn next(self) -> BoundaryType {
+        match self {
+            BoundaryType::Wall => BoundaryType::Void,
+            BoundaryType::Void => BoundaryType::Wrap,
+            BoundaryType::Wrap => BoundaryType::Inflow,
+            BoundaryType::Inflow => BoundaryType::Wall,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Boundary
+{
+    pub boundary_type: BoundaryType,
+    // material the inflow spawns
+    pub inflow_material: String,
+    // chance per update that the inflow fills a free cell along the edge
+    pub inflow_prob: f32,
+    #[serde(skip)]
+    pub inflow_type: CellType,
+}
+
+impl Boundary {
+    pub fn new(boundary_type: BoundaryType) -> Self {
+        Boundary { boundary_type, inflow_material: String::from("Water"), inflow_prob: 0.05, inflow_type: CellType::Water }
+    }
+}
+
+// where a cell moving to a position ends up
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveTarget
+{
+    Cell(IVec2),
+    // out of the grid through a void edge
+    Void,
+    Blocked,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Boundaries
+{
+    pub left: Boundary,
+    pub right: Boundary,
+    pub bottom: Boundary,
+    pub top: Boundary,
+}
+
+impl Boundaries {
+    pub fn get(&self, edge: Edge) -> &Boundary {
+        match edge {
+            Edge::Left => &self.left,
+            Edge::Right => &self.right,
+            Edge::Bottom => &self.bottom,
+            Edge::Top => &self.top,
+        }
+    }
+
+    pub fn get_mut(&mut self, edge: Edge) -> &mut Boundary {
+        match edge {
+            Edge::Left => &mut self.left,
+            Edge::Right => &mut self.right,
+            Edge::Bottom => &mut self.bottom,
+            Edge::Top => &mut self.top,
+        }
+    }
+
+    pub fn boundary_type(&self, edge: Edge) -> BoundaryType {
+        self.get(edge).boundary_type
+    }
+
+    // looks up the inflow materials and checks that the wrapping edges come in pairs
+    pub fn resolve(&mut self, materials: &MaterialRegistry) -> Result<(), String>
+    {
+        for edge in Edge::ALL {
+            if (self.boundary_type(edge) == BoundaryType::Wrap) != (self.boundary_type(edge.opposite()) == BoundaryType::Wrap) {
+                return Err(format!("{:?} boundary wraps only when the {:?} one wraps too", edge, edge.opposite()));
+            }
+            let boundary = self.get_mut(edge);
+            boundary.inflow_type = materials.find(&boundary.inflow_material)
+                .ok_or(format!("{:?} boundary inflow of unknown material {}", edge, boundary.inflow_material))?;
+        }
+        Ok(())
+    }
+
+    pub fn move_target(&self, pos: IVec2, sizes: IVec2) -> MoveTarget
+    {
+        let mut target = pos;
+        let mut void = false;
+        for (axis, low_edge, high_edge) in [(0, Edge::Left, Edge::Right), (1, Edge::Bottom, Edge::Top)] {
+            let edge = if pos[axis] < 0 {
+                low_edge
+            } else if pos[axis] >= sizes[axis] {
+                high_edge
+            } else {
+                continue;
+            };
+            match self.boundary_type(edge) {
+                BoundaryType::Wrap => target[axis] = pos[axis].rem_euclid(sizes[axis]),
+                BoundaryType::Void => void = true,
+                BoundaryType::Wall | BoundaryType::Inflow => return MoveTarget::Blocked,
+            }
+        }
+        if void { MoveTarget::Void } else { MoveTarget::Cell(target) }
+    }
+
+    // chunks of the same checkerboard pass would meet across a wrapping edge,
+    // unless the grid is split into an even number of whole chunks along it
+    pub fn allows_parallel_update(&self, grid_sizes: IVec2) -> bool
+    {
+        [(0, Edge::Left), (1, Edge::Bottom)].iter().all(|(axis, edge)| {
+            self.boundary_type(*edge) != BoundaryType::Wrap || grid_sizes[*axis] % (2 * CHUNK_SIZE) == 0
+        })
+    }
+}
+
+impl CellGrid
+{
+    // inflow edges fill the air along them with their material
+    pub fn update_inflows(&mut self)
+    {
+        let sizes = self.cells.sizes;
+        for edge in Edge::ALL {
+            let boundary = self.settings.boundaries.get(edge);
+            if boundary.boundary_type != BoundaryType::Inflow {
+                continue;
+            }
+            let (inflow_type, inflow_prob) = (boundary.inflow_type, boundary.inflow_prob);
+            let (from, step, count) = match edge {
+                Edge::Left => (IVec2::ZERO, IVec2::Y, sizes.y),
+                Edge::Right => (IVec2::new(sizes.x - 1, 0), IVec2::Y, sizes.y),
+                Edge::Bottom => (IVec2::ZERO, IVec2::X, sizes.x),
+                Edge::Top => (IVec2::new(0, sizes.y - 1), IVec2::X, sizes.x),
+            };
+            for i in 0..count {
+                let pos = from + step * i;
+                if self.cells[pos].cell_type == CellType::Air && self.rng.random::<f32>() < inflow_prob {
+                    self.replace_cell(pos, inflow_type);
+                }
+            }
+        }
+    }
+
+    // the opposite edge follows into and out of wrapping, so the edges always wrap in pairs
+    pub fn set_boundary(&mut self, edge: Edge, boundary_type: BoundaryType, inflow_type: CellType)
+    {
+        let was_wrap = self.settings.boundaries.boundary_type(edge) == BoundaryType::Wrap;
+        let boundary = self.settings.boundaries.get_mut(edge);
+        boundary.boundary_type = boundary_type;
+        if boundary_type == BoundaryType::Inflow {
+            boundary.inflow_type = inflow_type;
+            boundary.inflow_material = self.materials.get(inflow_type).name.clone();
+        }
+        let opposite = self.settings.boundaries.get_mut(edge.opposite());
+        if boundary_type == BoundaryType::Wrap {
+            opposite.boundary_type = BoundaryType::Wrap;
+        } else if was_wrap {
+            opposite.boundary_type = BoundaryType::Wall;
+        }
+        // settled cells may be able to leave now, and the solids might have lost or gained a wall to hold onto
+        self.chunks.wake_all();
+        self.chunks.mark_solids_changed();
+    }
+}
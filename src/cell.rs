@@ -1,222 +1,291 @@
-use bevy::prelude::*;
-use image::math;
+use bevy_math::prelude::*;
+use bevy_color::prelude::*;
 use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use enum_map::Enum;
+#[cfg(feature = "app")]
+use bevy::prelude::Component;
 
 use crate::utils::rand_from_pos_i8;
 
-pub const CELL_TYPE_IS_SOLID_BIT: u8 = 0x80;
-pub const CELL_TYPE_IS_LIQUID_BIT: u8 = 0x40;
-pub const CELL_TYPE_IS_POWDER_BIT: u8 = 0x40;
-pub const CELL_TYPE_IS_DISSOLVABLE_BIT: u8 = 0x20;
-
-pub const CELL_CUSTOM_DATA_INIT: u16 = 0;
-
-pub const CELL_MOVE_UPDATE_X_BIT: u16 = 0x8000;
-pub const CELL_MOVE_UPDATE_Y_BIT: u16 = 0x4000;
-pub const CELL_MOVE_UPDATE_BITS: u16 = CELL_MOVE_UPDATE_X_BIT | CELL_MOVE_UPDATE_Y_BIT;
-pub const CELL_IGNITE_UPDATE_BIT: u16 = 0x2000;
-pub const CELL_UPDATE_STATE_BITS: u16 = CELL_MOVE_UPDATE_BITS | CELL_IGNITE_UPDATE_BIT;
-pub const CELL_USE_FIRE_COLOR_BIT: u16 = 0x1000;
-
-pub const CELL_FLUID_SLIDE_BIT: u16 = 0x800;
-pub const CELL_FLUID_SLIDE_DIR_BIT: u16 = 0x400;
-pub const CELL_FLUID_SLIDE_BITS: u16 = CELL_FLUID_SLIDE_BIT | CELL_FLUID_SLIDE_DIR_BIT;
-pub const CELL_POWDER_STUCK_BIT: u16 = 0x2;
-pub const CELL_ON_FIRE_BIT: u16 = 0x100;
-pub const CELL_TIMER_BITS: u16 = 0xFF;
-pub const CELL_MAX_TIMER: u16 = CELL_TIMER_BITS;
-
-pub type MoveUpdateBits = u16;
-
-#[repr(u8)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Enum, Component)]
-pub enum CellType {
+pub const CELL_MAX_TIMER: u16 = u8::MAX as u16;
+// temperature of the cells that weren't created from the material properties, in degrees Celsius
+pub const AMBIENT_TEMPERATURE: f32 = 20.0;
+
+// id of a material in the MaterialRegistry, the built-in materials always have the same ids
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "app", derive(Component))]
+pub struct CellType(pub u8);
+
+#[allow(non_upper_case_globals)]
+impl CellType {
     // gasses
-    Air = 0,
-    Smoke = 1,
-    FlammableGass = 2,
-    Fire = 3,
-    Steam = 4,
+    pub const Air: CellType = CellType(0);
+    pub const Smoke: CellType = CellType(1);
+    pub const FlammableGass: CellType = CellType(2);
+    pub const Fire: CellType = CellType(3);
+    pub const Steam: CellType = CellType(4);
     // liquids
-    Acid = CELL_TYPE_IS_LIQUID_BIT | 0,
-    Water = CELL_TYPE_IS_DISSOLVABLE_BIT | CELL_TYPE_IS_LIQUID_BIT | 0,
-    Oil = CELL_TYPE_IS_DISSOLVABLE_BIT | CELL_TYPE_IS_LIQUID_BIT | 1,
-    Lava = CELL_TYPE_IS_DISSOLVABLE_BIT | CELL_TYPE_IS_LIQUID_BIT | 2,
+    pub const Water: CellType = CellType(5);
+    pub const Oil: CellType = CellType(6);
+    pub const Acid: CellType = CellType(7);
+    pub const Lava: CellType = CellType(8);
     // solids - stable
-    Glass = CELL_TYPE_IS_SOLID_BIT | 0,
-    Stone = CELL_TYPE_IS_SOLID_BIT | CELL_TYPE_IS_DISSOLVABLE_BIT | 0,
-    Wood = CELL_TYPE_IS_SOLID_BIT | CELL_TYPE_IS_DISSOLVABLE_BIT | 1,
-    Ice = CELL_TYPE_IS_SOLID_BIT | CELL_TYPE_IS_DISSOLVABLE_BIT | 2,
+    pub const Stone: CellType = CellType(9);
+    pub const Wood: CellType = CellType(10);
+    pub const Glass: CellType = CellType(11);
+    pub const Ice: CellType = CellType(12);
     // solids - powders
-    Sand = CELL_TYPE_IS_SOLID_BIT | CELL_TYPE_IS_DISSOLVABLE_BIT | CELL_TYPE_IS_POWDER_BIT | 0,
-    Coal = CELL_TYPE_IS_SOLID_BIT | CELL_TYPE_IS_DISSOLVABLE_BIT | CELL_TYPE_IS_POWDER_BIT | 1,
+    pub const Sand: CellType = CellType(13);
+    pub const Coal: CellType = CellType(14);
+
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
 }
 
-impl CellType {
+// how the cells of a material move, every material has exactly one
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CellPhase {
+    Gass,
+    Liquid,
+    Powder,
+    StaticSolid,
+}
+
+impl CellPhase {
+    // powders are solids too
     pub fn is_solid(&self) -> bool {
-        (*self as u8) & 0x80 > 0
+        matches!(self, CellPhase::Powder | CellPhase::StaticSolid)
+    }
+}
+
+// per cell state, every flag owns a single bit that no other flag uses
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CellFlags(u8);
+
+impl CellFlags {
+    pub const NONE: CellFlags = CellFlags(0);
+    pub const MOVE_UPDATE_X: CellFlags = CellFlags(1 << 0);
+    pub const MOVE_UPDATE_Y: CellFlags = CellFlags(1 << 1);
+    pub const IGNITE_UPDATE: CellFlags = CellFlags(1 << 2);
+    pub const USE_FIRE_COLOR: CellFlags = CellFlags(1 << 3);
+    pub const FLUID_SLIDE: CellFlags = CellFlags(1 << 4);
+    pub const FLUID_SLIDE_DIR: CellFlags = CellFlags(1 << 5);
+    pub const POWDER_STUCK: CellFlags = CellFlags(1 << 6);
+    pub const ON_FIRE: CellFlags = CellFlags(1 << 7);
+
+    pub const ALL: [CellFlags; 8] = [
+        CellFlags::MOVE_UPDATE_X,
+        CellFlags::MOVE_UPDATE_Y,
+        CellFlags::IGNITE_UPDATE,
+        CellFlags::USE_FIRE_COLOR,
+        CellFlags::FLUID_SLIDE,
+        CellFlags::FLUID_SLIDE_DIR,
+        CellFlags::POWDER_STUCK,
+        CellFlags::ON_FIRE,
+    ];
+
+    pub const MOVE_UPDATE: CellFlags = CellFlags::MOVE_UPDATE_X.union(CellFlags::MOVE_UPDATE_Y);
+    // cleared at the start of every frame
+    pub const UPDATE_STATE: CellFlags = CellFlags::MOVE_UPDATE.union(CellFlags::IGNITE_UPDATE);
+    pub const FLUID_SLIDE_STATE: CellFlags = CellFlags::FLUID_SLIDE.union(CellFlags::FLUID_SLIDE_DIR);
+
+    pub const fn union(self, other: CellFlags) -> CellFlags {
+        CellFlags(self.0 | other.0)
+    }
+
+    pub const fn contains(self, other: CellFlags) -> bool {
+        self.0 & other.0 == other.0
     }
 
-    pub fn is_liquid(&self) -> bool {
-        (*self as u8) & 0xC0 == 0x40
+    pub const fn intersects(self, other: CellFlags) -> bool {
+        self.0 & other.0 != 0
     }
 
-    pub fn is_gass(&self) -> bool {
-        (*self as u8) & 0xC0 == 0x00
+    pub fn insert(&mut self, other: CellFlags) {
+        self.0 |= other.0;
     }
 
-    pub fn is_powder(&self) -> bool {
-        (*self as u8) & 0xC0 == 0xC0
+    pub fn remove(&mut self, other: CellFlags) {
+        self.0 &= !other.0;
     }
 
-    pub fn is_dissolvable(&self) -> bool {
-        (*self as u8) & 0x20 == 0x20
+    pub fn toggle(&mut self, other: CellFlags) {
+        self.0 ^= other.0;
+    }
+
+    pub fn set(&mut self, other: CellFlags, value: bool) {
+        if value {
+            self.insert(other);
+        } else {
+            self.remove(other);
+        }
     }
 }
 
-#[derive(Clone, Copy)]
+// fails to compile when two flags share a bit
+const _: () = {
+    let mut used = 0u8;
+    let mut i = 0;
+    while i < CellFlags::ALL.len() {
+        let bits = CellFlags::ALL[i].0;
+        assert!(bits.count_ones() == 1 && used & bits == 0);
+        used |= bits;
+        i += 1;
+    }
+};
+
+#[derive(Clone, Copy, PartialEq)]
 pub struct Cell
 {
     pub cell_type: CellType,
     pub color_offset: i8,
-    pub custom_data: u16,
+    pub flags: CellFlags,
+    // burn or smoke duration
+    pub timer: u8,
+    // in degrees Celsius
+    pub temperature: f32,
+    // only gasses hold it, burning gasses build it up
+    pub pressure: f32,
+    // cells per update, powders and liquids speed up while falling through gasses
+    pub fall_speed: f32,
+    // electric charge of conductors, FULL_CHARGE when the current has just reached the cell
+    pub charge: u8,
+    // state of the logic, plant and creature cells, like the hold times of the gate inputs, the sap of a plant or the age of a creature
+    pub custom_data: u8,
 }
 
 impl Cell {
 
     pub fn default_air() -> Self {
-        Cell { cell_type: CellType::Air, color_offset: 0, custom_data: CELL_CUSTOM_DATA_INIT }
+        Cell::new(CellType::Air, 0)
     }
 
-    pub fn new(cell_type: CellType, custom_data: u16, color_offset: i8) -> Self
+    pub fn new(cell_type: CellType, color_offset: i8) -> Self
     {
-        Cell { cell_type, color_offset, custom_data }
+        Cell { cell_type, color_offset, flags: CellFlags::NONE, timer: 0, temperature: AMBIENT_TEMPERATURE, pressure: 0.0, fall_speed: 0.0, charge: 0, custom_data: 0 }
     }
 
-    pub fn color_scale(&self) -> f32 {
-        1.0 + (self.color_offset as f32) / 128.0
-    }
-
-    pub fn is_solid(&self) -> bool {
-        self.cell_type.is_solid()
-    }
-
-    pub fn is_liquid(&self) -> bool {
-        self.cell_type.is_liquid()
-    }
-
-    pub fn is_gass(&self) -> bool {
-        self.cell_type.is_gass()
-    }
-
-    pub fn is_powder(&self) -> bool {
-        self.cell_type.is_powder()
+    pub fn from_properties(cell_type: CellType, properties: &CellTypeProperties, pos: IVec2, rng: &mut impl Rng) -> Self
+    {
+        let mut cell = Cell::new(cell_type, properties.gen_color_offset(pos, rng));
+        cell.set_timer(properties.timer);
+        cell.temperature = properties.temperature;
+        if cell_type == CellType::Fire {
+            cell.ignite();
+        }
+        cell
     }
 
-    pub fn is_dissolvable(&self) -> bool {
-        self.cell_type.is_dissolvable()
+    pub fn color_scale(&self) -> f32 {
+        1.0 + (self.color_offset as f32) / 128.0
     }
 
     pub fn reset_udpate_state(&mut self) {
-        self.custom_data &= !CELL_UPDATE_STATE_BITS;
+        self.flags.remove(CellFlags::UPDATE_STATE);
     }
 
     pub fn has_moved_this_frame(&self) -> bool {
-        self.custom_data & CELL_MOVE_UPDATE_BITS > 0
+        self.flags.intersects(CellFlags::MOVE_UPDATE)
     }
 
-    pub fn dir_to_move_update_bits(dir: IVec2) -> MoveUpdateBits {
-        let x_bit = if dir.x != 0 { CELL_MOVE_UPDATE_X_BIT } else { 0 };
-        let y_bit = if dir.y != 0 { CELL_MOVE_UPDATE_Y_BIT } else { 0 };
-        x_bit | y_bit
+    pub fn dir_to_move_update_flags(dir: IVec2) -> CellFlags {
+        let x_flag = if dir.x != 0 { CellFlags::MOVE_UPDATE_X } else { CellFlags::NONE };
+        let y_flag = if dir.y != 0 { CellFlags::MOVE_UPDATE_Y } else { CellFlags::NONE };
+        x_flag.union(y_flag)
     }
 
-    pub fn is_move_update_not_orhogonal(&self, move_update_bits: MoveUpdateBits) -> bool {
-        let current_move_update_bits = self.custom_data & CELL_MOVE_UPDATE_BITS;
-        (current_move_update_bits & move_update_bits) > 0
+    pub fn is_move_update_not_orhogonal(&self, move_update_flags: CellFlags) -> bool {
+        self.flags.intersects(move_update_flags)
     }
 
-    pub fn move_update(&mut self, move_update_bits: MoveUpdateBits) {
-        self.custom_data |= move_update_bits;
+    pub fn move_update(&mut self, move_update_flags: CellFlags) {
+        self.flags.insert(move_update_flags);
     }
 
     pub fn was_ignited_this_frame(&self) -> bool {
-        self.custom_data & CELL_IGNITE_UPDATE_BIT == CELL_IGNITE_UPDATE_BIT
+        self.flags.contains(CellFlags::IGNITE_UPDATE)
     }
 
     pub fn ignite_update(&mut self) {
-        self.custom_data |= CELL_IGNITE_UPDATE_BIT;
+        self.flags.insert(CellFlags::IGNITE_UPDATE);
     }
 
     pub fn uses_fire_color(&self) -> bool {
-        self.custom_data & CELL_USE_FIRE_COLOR_BIT == CELL_USE_FIRE_COLOR_BIT
+        self.flags.contains(CellFlags::USE_FIRE_COLOR)
     }
 
     pub fn use_fire_color(&mut self) {
-        self.custom_data |= CELL_USE_FIRE_COLOR_BIT;
+        self.flags.insert(CellFlags::USE_FIRE_COLOR);
     }
 
     pub fn dont_use_fire_color(&mut self) {
-        self.custom_data &= !CELL_USE_FIRE_COLOR_BIT;
+        self.flags.remove(CellFlags::USE_FIRE_COLOR);
     }
 
     pub fn does_fluid_slide(&self) -> bool {
-        self.custom_data & CELL_FLUID_SLIDE_BIT == CELL_FLUID_SLIDE_BIT
+        self.flags.contains(CellFlags::FLUID_SLIDE)
     }
 
-    pub fn gen_fluid_slide_dir(&mut self) {
-        let dir = rand::thread_rng().gen_range(0..2);
-        let fluid_slide = CELL_FLUID_SLIDE_BIT | (dir * CELL_FLUID_SLIDE_DIR_BIT);
-        self.custom_data = fluid_slide | (self.custom_data & !CELL_FLUID_SLIDE_BITS)
+    pub fn gen_fluid_slide_dir(&mut self, rng: &mut impl Rng) {
+        self.flags.insert(CellFlags::FLUID_SLIDE);
+        self.flags.set(CellFlags::FLUID_SLIDE_DIR, rng.random_range(0..2) == 1);
     }
 
     pub fn stop_fluid_slide(&mut self) {
-        self.custom_data &= !CELL_FLUID_SLIDE_BITS;
+        self.flags.remove(CellFlags::FLUID_SLIDE_STATE);
     }
 
     pub fn reverse_fluid_slide_dir(&mut self) {
-        self.custom_data ^= CELL_FLUID_SLIDE_DIR_BIT;
+        self.flags.toggle(CellFlags::FLUID_SLIDE_DIR);
     }
 
     pub fn get_fluid_slide_dir(&self) -> i32 {
-        if (self.custom_data & CELL_FLUID_SLIDE_DIR_BIT) > 0 { 1 } else { -1 }
+        if self.flags.contains(CellFlags::FLUID_SLIDE_DIR) { 1 } else { -1 }
     }
 
     pub fn is_on_fire(&self) -> bool {
-        self.custom_data & CELL_ON_FIRE_BIT == CELL_ON_FIRE_BIT
+        self.flags.contains(CellFlags::ON_FIRE)
     }
 
     pub fn get_timer(&self) -> u16 {
-        self.custom_data & CELL_TIMER_BITS
+        self.timer as u16
     }
 
     pub fn is_powder_stuck(&self) -> bool {
-        self.custom_data & CELL_POWDER_STUCK_BIT > 0
+        self.flags.contains(CellFlags::POWDER_STUCK)
     }
 
     pub fn set_powder_stuck(&mut self, stuck: bool) {
-        self.custom_data = (if stuck { CELL_POWDER_STUCK_BIT } else { 0 }) | (self.custom_data & !CELL_POWDER_STUCK_BIT);
+        self.flags.set(CellFlags::POWDER_STUCK, stuck);
     }
 
     pub fn ignite(&mut self) {
         self.ignite_update();
         self.use_fire_color();
-        self.custom_data |= CELL_ON_FIRE_BIT;
+        self.flags.insert(CellFlags::ON_FIRE);
     }
 
     pub fn extinguish(&mut self) {
         self.dont_use_fire_color();
-        self.custom_data &= !CELL_ON_FIRE_BIT;
+        self.flags.remove(CellFlags::ON_FIRE);
     }
 
     pub fn set_timer(&mut self, duration: u16) {
-        self.custom_data = duration | (self.custom_data & !CELL_TIMER_BITS);
+        self.timer = duration.min(CELL_MAX_TIMER) as u8;
     }
 }
 
-#[derive(Clone)]
+fn default_dispersion() -> u8 {
+    1
+}
+
+fn default_max_fall_speed() -> f32 {
+    1.0
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct CellTypeProperties
 {
     pub density: f32,
@@ -226,14 +295,37 @@ pub struct CellTypeProperties
     pub color_change_prob: f32,
     pub movement_prob: f32,
     pub fallthroug_prob: f32,
+    // chance per update that a liquid doesn't flow to the sides, thick liquids ooze
+    #[serde(default)]
+    pub viscosity: f32,
+    // how many cells a fluid can slide to the side in one update
+    #[serde(default = "default_dispersion")]
+    pub dispersion: u8,
+    // terminal velocity of falling powders and liquids in cells per update
+    #[serde(default = "default_max_fall_speed")]
+    pub max_fall_speed: f32,
+    // chance per update to catch fire above the ignition temperature, for air the chance to turn into flame next to a burning cell
     pub ignite_prob: f32,
+    // doesn't burn without it
+    #[serde(default)]
+    pub ignition_temperature: Option<f32>,
+    // fraction of the temperature difference to a neighbor evened out per update, the lower of the two applies
+    pub conductivity: f32,
+    // how much heat it takes to change the temperature
+    pub heat_capacity: f32,
+    // temperature of new cells
+    pub temperature: f32,
+    // blast force it withstands
+    pub strength: f32,
+    // pressure added every update while burning
+    pub burn_pressure: f32,
     // max value CELL_MAX_TIMER
     pub timer: u16,
     pub smoke_after_burnout: bool,
     pub fire_color_prob: f32,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum RandColorPattern
 {
     None,
@@ -241,14 +333,14 @@ pub enum RandColorPattern
 }
 
 impl CellTypeProperties {
-    pub fn gen_color_offset(&self, pos: IVec2) -> i8
+    pub fn gen_color_offset(&self, pos: IVec2, rng: &mut impl Rng) -> i8
     {
         match self.rand_color_pattern {
             RandColorPattern::None => {
-                ((rand::thread_rng().gen::<i8>() as f32) * self.color_rand_radius) as i8
+                ((rng.random::<i8>() as f32) * self.color_rand_radius) as i8
             },
             RandColorPattern::Stretched { amount, use_x, orig_prob } => {
-                let pos = if rand::thread_rng().gen_bool(orig_prob as f64) {
+                let pos = if rng.random_bool(orig_prob as f64) {
                     pos
                 } else {
                     if use_x {
@@ -270,12 +362,12 @@ impl CellTypeProperties {
         }
     }
 
-    pub fn gen_color_offset_shifted(&self, pos: IVec2, shift_by: i8) -> i8 {
-        self.gen_color_offset(pos).saturating_add(shift_by)
+    pub fn gen_color_offset_shifted(&self, pos: IVec2, shift_by: i8, rng: &mut impl Rng) -> i8 {
+        self.gen_color_offset(pos, rng).saturating_add(shift_by)
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum CellColors
 {
     CentricRGB { color: Color },
@@ -286,6 +378,13 @@ pub enum CellColors
     BackgroundGradient { from: Color, to: Color },
 }
 
+// fully transparent
+impl Default for CellColors {
+    fn default() -> Self {
+        CellColors::CentricRGBA { color: Color::NONE }
+    }
+}
+
 impl CellColors {
     pub fn get_color_rgba(&self, color_scale: f32, timer: u16, pos: Vec2, color_rand_radius: f32, max_timer: u16) -> Vec4
     {
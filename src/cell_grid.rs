@@ -1,39 +1,249 @@
-use std::cell;
+use std::marker::PhantomData;
 use std::mem::swap;
+use std::ops::{Index, IndexMut};
+use std::sync::Mutex;
 
 use rand::prelude::*;
-use enum_map::EnumMap;
+use serde::{Deserialize, Serialize};
+use enum_map::{Enum, EnumMap};
 
-use bevy::prelude::*;
+use bevy_math::prelude::*;
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+use rayon::prelude::*;
+#[cfg(feature = "app")]
+use bevy::prelude::Component;
 
-use crate::ui::BrushType;
 use crate::utils::*;
 use crate::cell::*;
+use crate::grid_chunks::*;
+use crate::materials::*;
+use crate::reactions::*;
+use crate::particles::*;
+use crate::rigid_bodies::*;
+use crate::gravity::*;
+use crate::boundaries::*;
+use crate::logic::*;
+use crate::liquid_pressure::*;
 
-pub struct CellGrid
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Enum)]
+#[cfg_attr(feature = "app", derive(Component))]
+pub enum BrushType
+{
+    Circle,
+    Square,
+    LineRound,
+    LineSharp,
+    // throws the cells in the direction of the drag
+    Fling,
+}
+
+// probabilities and switches driving the simulation rules
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CellGridSettings
 {
-    pub top_gass_leak: bool,
+    // what happens to the cells reaching the grid edges
+    pub boundaries: Boundaries,
+    // groups of static solids losing their support fall as rigid bodies
+    pub rigid_bodies: bool,
+    // static solids reaching too far from their anchoring crack or fall
+    pub structural_integrity: bool,
+    // connected bodies of liquid push their highest cells into the lowest free cells around them
+    pub liquid_pressure: bool,
+    // current travels along the conductors from the power sources
+    pub electricity: bool,
+    // plant parts drink water and grow
+    pub plants: bool,
     pub powder_fall_prob: f32,
     pub powder_liquid_stuck_prob: f32,
     pub liquid_fall_prob: f32,
-    pub acid_reaction_prob: f32,
-    pub neutralize_acid_prob: f32,
-    pub steam_liquify_prob: f32,
-    pub freeze_prob: f32,
-    pub lava_cooldown_prob: f32,
-    pub lava_ignite_prob: f32,
     pub fire_decrease_prob: f32,
     pub fire_solid_extinguish_prob: f32,
     pub smoke_decrease_prob: f32,
     pub smoke_degradation_prob: f32,
-    pub wood_flame_ash_prob: f32,
-    pub fire_color_prob: f32,
+    // fraction of the difference to the ambient temperature air loses per update
+    pub air_heat_loss: f32,
+    // fraction of the pressure gasses lose per update
+    pub pressure_release: f32,
+    // gass pressure that sets off a blast
+    pub blast_pressure: f32,
+    pub blast_radius_per_pressure: f32,
+    pub max_blast_radius: f32,
+    // speed per blast force, which powders and liquids are thrown with
+    pub blast_push: f32,
+    // direction of the gravity everywhere in the grid and its strength relative to the default one, zero for weightlessness
+    pub gravity: Vec2,
+    // points pulling the cells towards themselves in addition to the uniform gravity
+    pub gravity_points: Vec<GravityPoint>,
+    // speed falling cells, particles and rigid bodies gain per update under the default gravity
+    pub particle_gravity: f32,
+    pub max_particle_speed: f32,
+    // particles falling into a liquid faster than this throw it up
+    pub splash_speed: f32,
+    // speed of the flung cells per cell of the drag
+    pub fling_speed: f32,
+    // updates between the searches for the unsupported static solids
+    pub body_check_interval: u32,
+    // force per speed of a rigid body hitting something, the cells weaker than it break off
+    pub body_impact_strength: f32,
+    pub integrity_check_interval: u32,
+    // how far along the static solids a cell can reach from its anchoring per its strength
+    pub integrity_reach_per_strength: f32,
+    // extra reach every powder or liquid cell lying on a static solid takes
+    pub integrity_load: f32,
+    pub liquid_pressure_interval: u32,
+    // cells moved per body of liquid at once
+    pub liquid_pressure_flow: u32,
+    // difference of the liquid levels in cells from which the liquid shoots out as a jet
+    pub jet_head: f32,
+    // heat a live conductor gives to every touching liquid or solid per update
+    pub electric_heat: f32,
+    // flammable gasses and liquids touching a live conductor are heated at least to it
+    pub spark_temperature: f32,
+    // chance per update that the growth timer of a plant cell runs down
+    pub plant_growth_prob: f32,
+}
+
+// heat is exchanged only with the orthogonal neighbors
+const HEAT_OFFSETS: [IVec2; 4] = [IVec2::new(1, 0), IVec2::new(0, 1), IVec2::new(-1, 0), IVec2::new(0, -1)];
+// smaller temperature differences don't keep the cells awake
+const SETTLED_TEMPERATURE_DIFF: f32 = 0.5;
+// smaller pressure is released at once
+const SETTLED_PRESSURE: f32 = 0.1;
+
+// explosion centered at the position, its force decreases to zero at the radius
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Blast
+{
+    pub pos: IVec2,
+    pub radius: f32,
+    pub power: f32,
+    // blown by the pressure of a gass pocket, which an earlier blast might have released already
+    pub from_pocket: bool,
+}
+
+pub struct CellGrid
+{
+    pub settings: CellGridSettings,
     pub cells: Vector2D<Cell>,
-    pub cell_properties: EnumMap<CellType, CellTypeProperties>,
+    pub materials: MaterialRegistry,
+    pub reactions: ReactionTable,
+    pub chunks: GridChunks,
+    // cells flying above the grid
+    pub particles: Vec<Particle>,
+    pub bodies: Vec<RigidBody>,
+    // doors opened by the current, they close again once it's gone
+    pub open_doors: Vec<OpenDoor>,
+    pub liquid_pressure_state: LiquidPressureState,
+    pub body_search_state: BodySearchState,
+    pub updates: u64,
+    // blasts that went off since the start
+    pub blasts_fired: u64,
+    // all the randomness of the simulation comes from here, so the same seed gives the same frames
+    pub rng: StdRng,
+    // each chunk draws from its own generator, so the order in which the chunks are updated doesn't matter
+    pub chunk_rngs: Vec<StdRng>,
+    // update the chunks of one checkerboard pass on multiple threads, ignored without the parallel feature
+    pub parallel_update: bool,
+}
+
+// farthest a cell falls or slides in one update, the chunk updates of one pass must stay a chunk apart
+const MAX_CELL_MOVE: i32 = CHUNK_SIZE / 2 - 1;
+
+// view of the grid cells shared by the chunk updates of one pass, possibly running on different threads
+// cells are only accessed within MAX_CELL_MOVE + 1 cells from the updated chunk, so chunks of the same
+// checkerboard pass, which are a whole chunk apart, never touch the same cell
+#[derive(Clone, Copy)]
+struct SharedCells<'a>
+{
+    ptr: *mut Cell,
+    sizes: IVec2,
+    _marker: PhantomData<&'a mut Cell>,
+}
+
+unsafe impl Send for SharedCells<'_> {}
+unsafe impl Sync for SharedCells<'_> {}
+
+impl<'a> SharedCells<'a> {
+    fn new(cells: &'a mut Vector2D<Cell>) -> Self {
+        SharedCells { ptr: cells.data.as_mut_ptr(), sizes: cells.sizes, _marker: PhantomData }
+    }
+
+    fn is_in_range(&self, iv: IVec2) -> bool {
+        iv.x >= 0 && iv.y >= 0 && iv.x < self.sizes.x && iv.y < self.sizes.y
+    }
+}
+
+impl Index<IVec2> for SharedCells<'_> {
+    type Output = Cell;
+
+    fn index(&self, index: IVec2) -> &Self::Output {
+        assert!(self.is_in_range(index));
+        unsafe { &*self.ptr.add((index.x + self.sizes.x * index.y) as usize) }
+    }
+}
+
+impl IndexMut<IVec2> for SharedCells<'_> {
+    fn index_mut(&mut self, index: IVec2) -> &mut Self::Output {
+        assert!(self.is_in_range(index));
+        unsafe { &mut *self.ptr.add((index.x + self.sizes.x * index.y) as usize) }
+    }
+}
+
+// updates the cells of a single chunk
+struct ChunkUpdater<'a>
+{
+    settings: &'a CellGridSettings,
+    materials: &'a MaterialRegistry,
+    reactions: &'a ReactionTable,
+    cells: SharedCells<'a>,
+    chunks: &'a GridChunks,
+    rng: &'a mut StdRng,
+    // blasts reach beyond the neighboring chunks, so they are applied after all the passes
+    blasts: &'a Mutex<Vec<Blast>>,
 }
 
 impl CellGrid
 {
+    pub fn set_seed(&mut self, seed: u64)
+    {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.chunk_rngs = gen_chunk_rngs(self.chunks.count(), &mut self.rng);
+    }
+
+    // crops or pads the grid with air, the bottom left corner stays in place so the cells keep lying on the floor
+    pub fn resize(&mut self, sizes: IVec2)
+    {
+        let sizes = sizes.max(IVec2::ONE);
+        if sizes == self.cells.sizes {
+            return;
+        }
+        let mut cells = Vector2D::<Cell>::new(sizes, Cell::default_air());
+        let common_sizes = sizes.min(self.cells.sizes);
+        for y in 0..common_sizes.y {
+            for x in 0..common_sizes.x {
+                let pos = IVec2::new(x, y);
+                cells[pos] = self.cells[pos];
+            }
+        }
+        self.cells = cells;
+        self.chunks = GridChunks::new(sizes);
+        self.chunk_rngs = gen_chunk_rngs(self.chunks.count(), &mut self.rng);
+        self.liquid_pressure_state = LiquidPressureState::default();
+        self.body_search_state = BodySearchState::default();
+        // the cropped off cells of the bodies are lost with the rest
+        let in_grid = |pos: &IVec2| pos.x < sizes.x && pos.y < sizes.y;
+        self.particles.retain(|particle| in_grid(&particle.cell_pos()));
+        for body in self.bodies.iter_mut() {
+            (body.cells, body.placed) = body.cells.iter().zip(body.placed.iter())
+                .filter(|(_, placed)| placed.as_ref().is_none_or(in_grid))
+                .map(|(cell, placed)| (*cell, *placed))
+                .unzip();
+        }
+        for door in self.open_doors.iter_mut() {
+            door.cells.retain(in_grid);
+        }
+    }
+
     pub fn set_cells(&mut self, pos: IVec2, prev_pos: Option<IVec2>, brush: BrushType, size: i32, cell_type: CellType, replace_solids: bool)
     {
         match brush {
@@ -69,16 +279,19 @@ impl CellGrid
                     self.set_cells_line_sharp(prev_pos, pos, size, cell_type, replace_solids);
                 }
             },
+            BrushType::Fling => {
+                if let Some(prev_pos) = prev_pos {
+                    self.fling_cells(prev_pos, pos, size, replace_solids);
+                }
+            },
         }
     }
 
     pub fn set_cells_circle(&mut self, pos: IVec2, size: i32, cell_type: CellType, replace_solids: bool)
     {
         let mut set_cell = |pos: IVec2| {
-            if self.cells.is_in_range(pos) && (replace_solids || !self.cells[pos].is_solid()) {
-                if self.cells[pos].cell_type != cell_type {
-                    self.cells[pos] = self.new_cell(cell_type, pos);
-                }
+            if self.cells.is_in_range(pos) && (replace_solids || !self.is_solid(pos)) && self.cells[pos].cell_type != cell_type {
+                self.replace_cell(pos, cell_type);
             }
         };
         
@@ -92,10 +305,8 @@ impl CellGrid
         for y in start_pos.y..end_pos.y {
             for x in start_pos.x..end_pos.x {
                 let iv = IVec2::new(x, y);
-                if self.cells.is_in_range(iv) && (replace_solids || !self.cells[iv].is_solid()) {
-                    if self.cells[iv].cell_type != cell_type {
-                        self.cells[iv] = self.new_cell(cell_type, iv);
-                    }
+                if self.cells.is_in_range(iv) && (replace_solids || !self.is_solid(iv)) && self.cells[iv].cell_type != cell_type {
+                    self.replace_cell(iv, cell_type);
                 }
             }
         }
@@ -104,10 +315,8 @@ impl CellGrid
     pub fn set_cells_square_line(&mut self, pos_from: IVec2, pos_to: IVec2, size: i32, cell_type: CellType, replace_solids: bool)
     {
         let mut set_cell = |pos: IVec2| {
-            if self.cells.is_in_range(pos) && (replace_solids || !self.cells[pos].is_solid()) {
-                if self.cells[pos].cell_type != cell_type {
-                    self.cells[pos] = self.new_cell(cell_type, pos);
-                }
+            if self.cells.is_in_range(pos) && (replace_solids || !self.is_solid(pos)) && self.cells[pos].cell_type != cell_type {
+                self.replace_cell(pos, cell_type);
             }
         };
         
@@ -119,10 +328,8 @@ impl CellGrid
     pub fn set_cells_line_round(&mut self, pos_from: IVec2, pos_to: IVec2, size: i32, cell_type: CellType, replace_solids: bool)
     {
         let mut set_cell = |pos: IVec2| {
-            if self.cells.is_in_range(pos) && (replace_solids || !self.cells[pos].is_solid()) {
-                if self.cells[pos].cell_type != cell_type {
-                    self.cells[pos] = self.new_cell(cell_type, pos);
-                }
+            if self.cells.is_in_range(pos) && (replace_solids || !self.is_solid(pos)) && self.cells[pos].cell_type != cell_type {
+                self.replace_cell(pos, cell_type);
             }
         };
         
@@ -138,41 +345,178 @@ impl CellGrid
         }
 
         let mut set_cell = |pos: IVec2| {
-            if self.cells.is_in_range(pos) && (replace_solids || !self.cells[pos].is_solid()) {
-                if self.cells[pos].cell_type != cell_type {
-                    self.cells[pos] = self.new_cell(cell_type, pos);
-                }
+            if self.cells.is_in_range(pos) && (replace_solids || !self.is_solid(pos)) && self.cells[pos].cell_type != cell_type {
+                self.replace_cell(pos, cell_type);
             }
         };
         
         dda_thick(pos_from, pos_to, size, &mut set_cell);
     }
 
+    pub fn is_solid(&self, pos: IVec2) -> bool {
+        self.materials.is_solid(self.cells[pos].cell_type)
+    }
+
+    pub(crate) fn replace_cell(&mut self, pos: IVec2, cell_type: CellType) {
+        if self.materials.is_static_solid(self.cells[pos].cell_type) || self.materials.is_static_solid(cell_type) {
+            self.chunks.mark_solids_changed();
+        }
+        self.cells[pos] = Cell::from_properties(cell_type, &self.materials[cell_type], pos, &mut self.rng);
+        self.chunks.wake_cell(pos);
+    }
+
     pub fn update(&mut self, even_update_num: bool)
     {
-        for y in 0..self.cells.sizes.y {
-            for x in 0..self.cells.sizes.x {
-                let pos = IVec2::new(x, y);
-                self.cells[pos].reset_udpate_state();
+        // only the cells woken up during the last frame are updated, the rest of the chunks sleep
+        self.chunks.start_frame();
+        for chunk in self.chunks.chunks.data.iter() {
+            let rect = chunk.current;
+            for y in rect.min.y..=rect.max.y {
+                for x in rect.min.x..=rect.max.x {
+                    self.cells[IVec2::new(x, y)].reset_udpate_state();
+                }
             }
         }
 
-        for y in (0..self.cells.sizes.y).rev() {
-            let x_range: Box<dyn Iterator<Item=i32>> = if even_update_num { Box::new(0..self.cells.sizes.x)  } else { Box::new((0..self.cells.sizes.x).rev()) };
-            for x in x_range {
-                let pos = IVec2::new(x, y);
-                if self.cells[pos].is_gass() {
-                    self.update_cell(pos);
-                }
+        // chunks with the same parity aren't neighbors, so they can be updated in any order or at once
+        let cells = SharedCells::new(&mut self.cells);
+        let settings = &self.settings;
+        let materials = &self.materials;
+        let reactions = &self.reactions;
+        let chunks = &self.chunks;
+        let chunk_sizes = chunks.sizes();
+        let blasts = Mutex::new(Vec::new());
+        let blasts_ref = &blasts;
+        #[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+        let parallel_update = self.parallel_update && settings.boundaries.allows_parallel_update(chunks.grid_sizes);
+        for pass in 0..4 {
+            let parity = IVec2::new(pass % 2, pass / 2);
+            let in_pass = |(index, _): &(usize, &mut StdRng)| {
+                let chunk_pos = IVec2::new(*index as i32 % chunk_sizes.x, *index as i32 / chunk_sizes.x);
+                chunk_pos % 2 == parity && !chunks.chunks[chunk_pos].is_sleeping()
+            };
+            let update_chunk = |(index, rng): (usize, &mut StdRng)| {
+                let mut updater = ChunkUpdater { settings, materials, reactions, cells, chunks, rng, blasts: blasts_ref };
+                updater.update_chunk(chunks.chunks.data[index].current, even_update_num);
+            };
+            #[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+            if parallel_update {
+                self.chunk_rngs.par_iter_mut().enumerate().filter(in_pass).for_each(update_chunk);
+                continue;
             }
+            self.chunk_rngs.iter_mut().enumerate().filter(in_pass).for_each(update_chunk);
         }
-        for y in 0..self.cells.sizes.y {
-            let x_range: Box<dyn Iterator<Item=i32>> = if even_update_num { Box::new(0..self.cells.sizes.x)  } else { Box::new((0..self.cells.sizes.x).rev()) };
-            for x in x_range {
-                let pos = IVec2::new(x, y);
-                if !self.cells[pos].is_gass() {
-                    self.update_cell(pos);
+
+        // sorted, so the order in which the chunks found them doesn't matter
+        // the detonations go first and blow up the pockets around them
+        let mut blasts = blasts.into_inner().unwrap();
+        blasts.sort_by_key(|blast| (blast.from_pocket, blast.pos.y, blast.pos.x));
+        for blast in blasts {
+            // the pocket might have been blown up already by a blast next to it, detonated explosives always go off
+            if !blast.from_pocket || self.cells[blast.pos].pressure >= self.settings.blast_pressure {
+                self.blast(&blast);
+                self.blasts_fired += 1;
+            }
+        }
+
+        self.update_inflows();
+        self.update_particles();
+        self.update_rigid_bodies();
+        self.update_structural_integrity();
+        self.update_liquid_pressure();
+        self.update_electricity();
+        self.update_plants();
+        self.update_creatures();
+        self.updates += 1;
+    }
+
+    // destroys the solids weaker than the blast force, throws the powders and liquids away and heats up everything
+    pub fn blast(&mut self, blast: &Blast)
+    {
+        let radius = blast.radius.ceil() as i32;
+        let fire_temperature = self.materials[CellType::Fire].temperature;
+        let mut thrown = Vec::new();
+        for y in -radius..=radius {
+            for x in -radius..=radius {
+                let pos = blast.pos + IVec2::new(x, y);
+                let dist = IVec2::new(x, y).as_vec2().length();
+                if !self.cells.is_in_range(pos) || dist > blast.radius {
+                    continue;
+                }
+                let falloff = 1.0 - dist / blast.radius;
+                let force = blast.power * falloff;
+                let cell_type = self.cells[pos].cell_type;
+                match self.materials.phase(cell_type) {
+                    CellPhase::Gass => {
+                        if falloff > 0.5 {
+                            self.replace_cell(pos, CellType::Fire);
+                        }
+                        self.cells[pos].pressure = 0.0;
+                    },
+                    CellPhase::StaticSolid => {
+                        // the destroyed solids go up in flames near the center and in smoke further away
+                        if force > self.materials[cell_type].strength {
+                            self.replace_cell(pos, if falloff > 0.5 { CellType::Fire } else { CellType::Smoke });
+                        }
+                    },
+                    CellPhase::Liquid | CellPhase::Powder => {
+                        if force > self.materials[cell_type].strength && dist > 0.0 {
+                            thrown.push((pos, dist, force));
+                        }
+                    },
                 }
+                let temperature = self.cells[pos].temperature.max(fire_temperature * falloff);
+                self.cells[pos].temperature = temperature;
+                self.chunks.wake_cell(pos);
+            }
+        }
+        for (pos, dist, force) in thrown {
+            let dir = (pos - blast.pos).as_vec2() / dist;
+            self.launch_cell(pos, dir * force * self.settings.blast_push);
+        }
+    }
+}
+
+pub fn gen_chunk_rngs(count: usize, rng: &mut StdRng) -> Vec<StdRng>
+{
+    (0..count).map(|_| StdRng::from_rng(rng)).collect()
+}
+
+impl ChunkUpdater<'_>
+{
+    fn is_solid(&self, pos: IVec2) -> bool {
+        self.materials.is_solid(self.cells[pos].cell_type)
+    }
+
+    fn is_liquid(&self, pos: IVec2) -> bool {
+        self.materials.is_liquid(self.cells[pos].cell_type)
+    }
+
+    fn is_gass(&self, pos: IVec2) -> bool {
+        self.materials.is_gass(self.cells[pos].cell_type)
+    }
+
+    fn is_powder(&self, pos: IVec2) -> bool {
+        self.materials.is_powder(self.cells[pos].cell_type)
+    }
+
+    fn update_chunk(&mut self, rect: DirtyRect, even_update_num: bool)
+    {
+        for y in (rect.min.y..=rect.max.y).rev() {
+            self.update_row(rect, y, true, even_update_num);
+        }
+        for y in rect.min.y..=rect.max.y {
+            self.update_row(rect, y, false, even_update_num);
+        }
+    }
+
+    fn update_row(&mut self, rect: DirtyRect, y: i32, gasses: bool, even_update_num: bool)
+    {
+        let x_range: Box<dyn Iterator<Item=i32>> = if even_update_num { Box::new(rect.min.x..=rect.max.x) } else { Box::new((rect.min.x..=rect.max.x).rev()) };
+        for x in x_range {
+            let pos = IVec2::new(x, y);
+            if self.is_gass(pos) == gasses {
+                self.update_cell(pos);
             }
         }
     }
@@ -183,33 +527,267 @@ impl CellGrid
             return;
         }
         self.update_color(pos);
-        if self.cells[pos].is_solid() {
-            if self.cells[pos].is_powder() {
+        // a cell that changed into another material starts moving in the next frame
+        if self.update_temperature(pos) || self.react(pos) {
+            return;
+        }
+        if self.is_solid(pos) {
+            if self.is_powder(pos) {
                 self.update_powder(pos);
-            } else if self.cells[pos].cell_type == CellType::Ice {
-                self.update_ice(pos);
             }
-        } else if self.cells[pos].is_liquid() {
+        } else if self.is_liquid(pos) {
             self.update_liquid(pos);
-            if self.cells[pos].cell_type == CellType::Acid {
-                self.update_acid(pos);
-            } else if self.cells[pos].cell_type == CellType::Lava {
-                self.update_lava(pos);
-            } else if self.cells[pos].cell_type == CellType::Water {
-                self.update_water(pos);
-            }
-        } else if self.cells[pos].cell_type != CellType::Air { // is gass, but not air
+        } else {
+            self.update_pressure(pos);
             if self.cells[pos].cell_type == CellType::Smoke {
                 self.update_smoke(pos);
-            } else if self.cells[pos].cell_type == CellType::Steam {
-                self.update_steam(pos);
             }
-            self.update_gass(pos);
+            if self.cells[pos].cell_type != CellType::Air {
+                self.update_gass(pos);
+            }
         }
 
         if self.cells[pos].is_on_fire() {
             self.update_fire(pos);
         }
+
+        // keep the chunk awake while the cell can still change
+        if !self.is_settled(pos) {
+            self.chunks.wake_cell(pos);
+        }
+    }
+
+    // settled cell can't move or react with its neighbors until something around it changes
+    fn is_settled(&self, pos: IVec2) -> bool
+    {
+        let cell = self.cells[pos];
+        if cell.is_on_fire() || cell.pressure >= SETTLED_PRESSURE || !self.is_thermally_settled(pos) {
+            return false;
+        }
+        let phase = self.materials.phase(cell.cell_type);
+        if phase == CellPhase::Gass {
+            return cell.cell_type == CellType::Air;
+        }
+        let can_move_to = |off: IVec2| {
+            let target = self.move_target(pos + off);
+            if phase == CellPhase::Liquid { self.fluid_can_move_into(target, cell.cell_type, true) } else { self.can_move_into(target) }
+        };
+        if self.can_react(pos) {
+            return false;
+        }
+        // nothing falls without gravity
+        let Some(dirs) = self.gravity_dirs(pos, true) else {
+            return true;
+        };
+        if phase == CellPhase::Powder {
+            return !can_move_to(dirs.down) && !can_move_to(dirs.diagonals[0]) && !can_move_to(dirs.diagonals[1]);
+        }
+        if phase == CellPhase::Liquid {
+            let moves = [dirs.down, dirs.diagonals[0], dirs.diagonals[1], dirs.sides[0], dirs.sides[1]];
+            return !moves.iter().any(|off| can_move_to(*off));
+        }
+        true
+    }
+
+    // no heat flows to the neighbors and no temperature threshold is crossed
+    fn is_thermally_settled(&self, pos: IVec2) -> bool
+    {
+        let cell = self.cells[pos];
+        let properties = &self.materials[cell.cell_type];
+        if cell.cell_type == CellType::Air && (cell.temperature - properties.temperature).abs() >= SETTLED_TEMPERATURE_DIFF {
+            return false;
+        }
+        if let Some(ignition_temperature) = properties.ignition_temperature {
+            if cell.temperature >= ignition_temperature {
+                return false;
+            }
+        }
+        HEAT_OFFSETS.iter().all(|off| {
+            let neighbor_pos = pos + *off;
+            !self.cells.is_in_range(neighbor_pos)
+                || (cell.temperature - self.cells[neighbor_pos].temperature).abs() < SETTLED_TEMPERATURE_DIFF
+                || properties.conductivity.min(self.materials[self.cells[neighbor_pos].cell_type].conductivity) <= 0.0
+        })
+    }
+
+    // out of the grid, where the cells can't leave through
+    fn is_wall(&self, pos: IVec2) -> bool {
+        !self.cells.is_in_range(pos) && self.move_target(pos) == MoveTarget::Blocked
+    }
+
+    fn move_target(&self, pos: IVec2) -> MoveTarget {
+        self.settings.boundaries.move_target(pos, self.cells.sizes)
+    }
+
+    // the cell can move into the target or out of the grid through it
+    fn can_move_into(&self, target: MoveTarget) -> bool {
+        match target {
+            MoveTarget::Cell(pos) => !self.is_solid(pos),
+            MoveTarget::Void => true,
+            MoveTarget::Blocked => false,
+        }
+    }
+
+    // fluids move only into lighter fluids, gasses into heavier ones
+    fn fluid_can_move_into(&self, target: MoveTarget, fluid_type: CellType, is_liquid: bool) -> bool {
+        match target {
+            MoveTarget::Cell(pos) => !self.is_solid(pos) && self.compare_densities(self.cells[pos].cell_type, fluid_type, is_liquid),
+            _ => self.can_move_into(target),
+        }
+    }
+
+    // the cell leaving through a void edge is deleted
+    fn move_cell(&mut self, pos: IVec2, target: MoveTarget) {
+        match target {
+            MoveTarget::Cell(to_pos) => self.swap_cells(pos, to_pos),
+            MoveTarget::Void => self.set_cell(pos, Cell::default_air()),
+            MoveTarget::Blocked => {},
+        }
+    }
+
+    // the neighbors across the wrapping edges are woken up too
+    fn wake_cell(&self, pos: IVec2)
+    {
+        self.chunks.wake_cell(pos);
+        let sizes = self.cells.sizes;
+        if pos.x > 0 && pos.y > 0 && pos.x < sizes.x - 1 && pos.y < sizes.y - 1 {
+            return;
+        }
+        for y in -1..2 {
+            for x in -1..2 {
+                let neighbor_pos = pos + IVec2::new(x, y);
+                if self.cells.is_in_range(neighbor_pos) {
+                    continue;
+                }
+                if let MoveTarget::Cell(wrapped_pos) = self.move_target(neighbor_pos) {
+                    self.chunks.wake_rect(wrapped_pos, wrapped_pos);
+                }
+            }
+        }
+    }
+
+    fn matches(&self, matcher: CellMatcher, pos: IVec2) -> bool {
+        if !self.cells.is_in_range(pos) {
+            return matcher == CellMatcher::Wall && self.is_wall(pos);
+        }
+        matcher.matches_cell(self.materials, &self.cells[pos])
+    }
+
+    fn can_react(&self, pos: IVec2) -> bool {
+        self.reactions.contact_rules(self.cells[pos].cell_type).any(|rule| {
+            self.matches(rule.cell, pos) && rule.neighborhood.offsets().iter().any(|off| self.matches(rule.neighbor, pos + *off))
+        })
+    }
+
+    // every neighborhood used by the rules of the cell gets one randomly picked neighbor, the first rule
+    // matching the picked neighbor decides if it reacts, so the specific rules have to come before the general ones
+    // returns if the cell turned into another material
+    fn react(&mut self, pos: IVec2) -> bool {
+        let cell_type = self.cells[pos].cell_type;
+        if !self.reactions.has_contact_rules(cell_type) {
+            return false;
+        }
+        let reactions = self.reactions;
+        let mut picked = EnumMap::<Neighborhood, Option<IVec2>>::default();
+        let mut decided = EnumMap::<Neighborhood, bool>::default();
+        for rule in reactions.contact_rules(cell_type) {
+            if decided[rule.neighborhood] || !self.matches(rule.cell, pos) {
+                continue;
+            }
+            let offsets = rule.neighborhood.offsets();
+            let neighbor_pos = *picked[rule.neighborhood].get_or_insert_with(|| pos + offsets[self.rng.random_range(0..offsets.len())]);
+            if !self.matches(rule.neighbor, neighbor_pos) {
+                continue;
+            }
+            decided[rule.neighborhood] = true;
+            let mut prob = rule.prob;
+            if let Some(catalyst) = rule.catalyst {
+                if offsets.iter().any(|off| self.matches(catalyst, pos + *off)) {
+                    prob *= rule.catalyst_multiplier;
+                }
+            }
+            if self.rng.random::<f32>() < prob {
+                self.apply_reaction(rule, pos, neighbor_pos);
+                return self.cells[pos].cell_type != cell_type;
+            }
+        }
+        false
+    }
+
+    fn apply_reaction(&mut self, rule: &ReactionRule, pos: IVec2, neighbor_pos: IVec2) {
+        if let Some(product) = rule.cell_product {
+            self.replace_cell(pos, product);
+        }
+        if let Some(product) = rule.neighbor_product {
+            self.replace_cell(neighbor_pos, product);
+        }
+        if let Some(product) = rule.byproduct {
+            let offsets = rule.neighborhood.offsets();
+            let start = self.rng.random_range(0..offsets.len());
+            for i in 0..offsets.len() {
+                let free_pos = pos + offsets[(start + i) % offsets.len()];
+                if self.cells.is_in_range(free_pos) && self.cells[free_pos].cell_type == CellType::Air {
+                    self.replace_cell(free_pos, product);
+                    break;
+                }
+            }
+        }
+    }
+
+    // exchanges heat with the orthogonal neighbors, then changes the phase or ignites the cell when it crosses a threshold
+    // returns if the cell turned into another material
+    fn update_temperature(&mut self, pos: IVec2) -> bool
+    {
+        let cell_type = self.cells[pos].cell_type;
+        let properties = &self.materials[cell_type];
+        // burning cells are the heat source of fire
+        if self.cells[pos].is_on_fire() {
+            self.cells[pos].temperature = self.cells[pos].temperature.max(self.materials[CellType::Fire].temperature);
+        }
+        if cell_type == CellType::Air {
+            let temperature = self.cells[pos].temperature;
+            self.cells[pos].temperature += (properties.temperature - temperature) * self.settings.air_heat_loss;
+        }
+        for off in HEAT_OFFSETS {
+            let neighbor_pos = pos + off;
+            if !self.cells.is_in_range(neighbor_pos) {
+                continue;
+            }
+            let neighbor_properties = &self.materials[self.cells[neighbor_pos].cell_type];
+            let temperature = self.cells[pos].temperature;
+            let neighbor_temperature = self.cells[neighbor_pos].temperature;
+            let conductivity = properties.conductivity.min(neighbor_properties.conductivity);
+            if conductivity <= 0.0 || (temperature - neighbor_temperature).abs() < SETTLED_TEMPERATURE_DIFF {
+                continue;
+            }
+            // both move towards the common temperature, so the heat is preserved
+            let capacity = properties.heat_capacity + neighbor_properties.heat_capacity;
+            let common_temperature = (properties.heat_capacity * temperature + neighbor_properties.heat_capacity * neighbor_temperature) / capacity;
+            self.cells[pos].temperature += conductivity * (common_temperature - temperature);
+            self.cells[neighbor_pos].temperature += conductivity * (common_temperature - neighbor_temperature);
+            // the neighbor passes the heat on by itself once awake
+            self.chunks.wake_rect(neighbor_pos, neighbor_pos);
+        }
+
+        let material = self.materials.get(cell_type);
+        let temperature = self.cells[pos].temperature;
+        let phase_change = match (&material.heating, &material.cooling) {
+            (Some(heating), _) if temperature > heating.temperature => Some(heating.cell_type),
+            (_, Some(cooling)) if temperature < cooling.temperature => Some(cooling.cell_type),
+            _ => None,
+        };
+        if let Some(new_type) = phase_change {
+            self.replace_cell(pos, new_type);
+            self.cells[pos].temperature = temperature;
+            return true;
+        }
+        if let Some(ignition_temperature) = properties.ignition_temperature {
+            if !self.cells[pos].is_on_fire() && temperature >= ignition_temperature && self.rng.random::<f32>() < properties.ignite_prob {
+                self.cells[pos].ignite();
+                self.chunks.wake_cell(pos);
+            }
+        }
+        false
     }
 
     fn update_color(&mut self, pos: IVec2) {
@@ -218,98 +796,200 @@ impl CellGrid
     }
 
     fn update_color_cell_type(&mut self, pos: IVec2, cell_type: CellType) {
-        if rand::rng().random::<f32>() < self.cell_properties[cell_type].color_change_prob {
-            self.cells[pos].color_offset = self.cell_properties[cell_type].gen_color_offset(pos);
+        if self.rng.random::<f32>() < self.materials[cell_type].color_change_prob {
+            self.cells[pos].color_offset = self.materials[cell_type].gen_color_offset(pos, self.rng);
         }
     }
 
     fn swap_cells(&mut self, from_pos: IVec2, to_pos: IVec2)
     {
-        let move_update_bits = Cell::dir_to_move_update_bits(to_pos - from_pos);
+        let move_update_flags = Cell::dir_to_move_update_flags(to_pos - from_pos);
         // ensure that movement doesn't exceed 2 units in both axis separately per frame
-        if self.cells[to_pos].cell_type != CellType::Air && self.cells[to_pos].is_move_update_not_orhogonal(move_update_bits) {
+        if self.cells[to_pos].cell_type != CellType::Air && self.cells[to_pos].is_move_update_not_orhogonal(move_update_flags) {
             return;
         }
-        // water extinguishing flame
-        if self.cells[from_pos].cell_type == CellType::Water && self.cells[to_pos].cell_type == CellType::Fire {
-            self.cells[to_pos] = self.new_cell(CellType::Smoke, to_pos);
-        }
         // swap
         let temp_cell = self.cells[from_pos];
         self.cells[from_pos] = self.cells[to_pos];
         self.cells[to_pos] = temp_cell;
         // register move updates
-        self.cells[from_pos].move_update(move_update_bits);
-        self.cells[to_pos].move_update(move_update_bits);
+        self.cells[from_pos].move_update(move_update_flags);
+        self.cells[to_pos].move_update(move_update_flags);
+        self.wake_cell(from_pos);
+        self.wake_cell(to_pos);
+        // the powder or liquid moving away might have held a static solid up
+        if !self.materials.is_gass(temp_cell.cell_type) && HEAT_OFFSETS.iter().any(|off| {
+            let neighbor_pos = from_pos + *off;
+            self.cells.is_in_range(neighbor_pos) && self.materials.is_static_solid(self.cells[neighbor_pos].cell_type)
+        }) {
+            self.chunks.mark_supports_changed();
+        }
     }
 
-    fn new_cell(&self, cell_type: CellType, pos: IVec2) -> Cell {
-        let mut cell = Cell::new(cell_type, CELL_CUSTOM_DATA_INIT, self.cell_properties[cell_type].gen_color_offset(pos));
-        cell.set_timer(self.cell_properties[cell_type].timer);
-        if cell_type == CellType::Fire {
-            cell.ignite();
-        }
-        if cell_type == CellType::Smoke {
-            cell.set_timer(self.cell_properties[cell_type].timer);
-        }
-        cell
+    fn set_cell(&mut self, pos: IVec2, cell: Cell) {
+        self.mark_solids_changed(pos, cell.cell_type);
+        self.cells[pos] = cell;
+        self.wake_cell(pos);
     }
 
-    fn compare_densities(&self, mut left: CellType, mut right: CellType, is_liquid: bool) -> bool {
-        if !is_liquid {
-            swap(&mut left, &mut right);
+    fn replace_cell(&mut self, pos: IVec2, cell_type: CellType) {
+        self.mark_solids_changed(pos, cell_type);
+        self.cells[pos] = self.new_cell(cell_type, pos);
+        self.wake_cell(pos);
+    }
+
+    // the structural integrity is checked again only after a static solid changed
+    fn mark_solids_changed(&self, pos: IVec2, cell_type: CellType) {
+        if self.materials.is_static_solid(self.cells[pos].cell_type) || self.materials.is_static_solid(cell_type) {
+            self.chunks.mark_solids_changed();
         }
-        self.cell_properties[left].density < self.cell_properties[right].density
     }
 
-    fn left_has_lower_density(&self, left: CellType, right: CellType) -> bool {
-        self.cell_properties[left].density < self.cell_properties[right].density
+    fn new_cell(&mut self, cell_type: CellType, pos: IVec2) -> Cell {
+        Cell::from_properties(cell_type, &self.materials[cell_type], pos, self.rng)
     }
 
-    fn left_has_greather_density(&self, left: CellType, right: CellType) -> bool {
-        self.cell_properties[left].density > self.cell_properties[right].density
+    fn compare_densities(&self, mut left: CellType, mut right: CellType, is_liquid: bool) -> bool {
+        if !is_liquid {
+            swap(&mut left, &mut right);
+        }
+        self.materials[left].density < self.materials[right].density
     }
 
-    fn rand_fallthrough(&self, pos: IVec2) -> bool {
-        rand::rng().random::<f32>() < self.cell_properties[self.cells[pos].cell_type].fallthroug_prob
+    fn rand_fallthrough(&mut self, pos: IVec2) -> bool {
+        self.rng.random::<f32>() < self.materials[self.cells[pos].cell_type].fallthroug_prob
     }
 
     fn update_powder(&mut self, pos: IVec2) {
-        if rand::rng().random::<f32>() > self.powder_fall_prob {
+        if self.rng.random::<f32>() > self.settings.powder_fall_prob {
             return;
         }
-        let bottom_pos = pos + IVec2::new(0, -1);
-        if self.cells.is_in_range(bottom_pos) {
-            if !self.cells[bottom_pos].is_solid() {
+        let Some(dirs) = self.gravity_dirs(pos, true) else {
+            return;
+        };
+        let bottom = self.move_target(pos + dirs.down);
+        if self.can_move_into(bottom) {
+            if self.fall(pos, dirs) {
+                self.cells[pos].set_powder_stuck(false);
+                return;
+            }
+            if let MoveTarget::Cell(bottom_pos) = bottom {
                 if self.rand_fallthrough(bottom_pos) {
                     self.cells[pos].set_powder_stuck(false);
                     self.swap_cells(pos, bottom_pos);
                 }
-                return;
-            }
-            if rand::rng().random::<f32>() > self.cell_properties[self.cells[pos].cell_type].movement_prob {
-                return;
             }
-            let bottom_left_dir = IVec2::new(-1, -1);
-            let bottom_right_dir = IVec2::new(1, -1);
-            let bottom_side_dir = if rand::random() { bottom_right_dir } else { bottom_left_dir };
-            let bottom_side_pos = pos + bottom_side_dir;
-            if self.cells.is_in_range(bottom_side_pos) && !self.cells[bottom_side_pos].is_solid() {
-                if self.cells[bottom_side_pos].is_liquid() {
+            return;
+        }
+        if self.land(pos, dirs.down) {
+            return;
+        }
+        if self.rng.random::<f32>() > self.materials[self.cells[pos].cell_type].movement_prob {
+            return;
+        }
+        let bottom_side_dir = if self.rng.random() { dirs.diagonals[1] } else { dirs.diagonals[0] };
+        let bottom_side = self.move_target(pos + bottom_side_dir);
+        if self.can_move_into(bottom_side) {
+            if let MoveTarget::Cell(bottom_side_pos) = bottom_side {
+                if self.is_liquid(bottom_side_pos) {
                     if self.cells[pos].is_powder_stuck() {
                         return;
                     }
-                    if rand::rng().random::<f32>() < self.powder_liquid_stuck_prob {
+                    if self.rng.random::<f32>() < self.settings.powder_liquid_stuck_prob {
                         self.cells[pos].set_powder_stuck(true);
                     }
                 }
-                if self.rand_fallthrough(bottom_side_pos) {
-                    self.swap_cells(pos, bottom_side_pos);
+                if !self.rand_fallthrough(bottom_side_pos) {
+                    return;
                 }
-                return;
             }
+            self.move_cell(pos, bottom_side);
+        }
+    }
+
+    // powders and liquids move along the local gravity, gasses against it
+    fn gravity_dirs(&self, pos: IVec2, along_gravity: bool) -> Option<GravityDirs>
+    {
+        let gravity = self.settings.gravity_at(pos.as_vec2() + 0.5);
+        GravityDirs::new(if along_gravity { gravity } else { -gravity })
+    }
+
+    // falls through the gasses below, several cells at once when it's fast enough
+    fn fall(&mut self, pos: IVec2, dirs: GravityDirs) -> bool
+    {
+        let max_speed = self.materials[self.cells[pos].cell_type].max_fall_speed.min(MAX_CELL_MOVE as f32);
+        let speed = (self.cells[pos].fall_speed + self.settings.particle_gravity * dirs.strength).min(max_speed);
+        let mut fall_pos = pos;
+        for _ in 0..(speed as i32).max(1) {
+            match self.move_target(fall_pos + dirs.down) {
+                MoveTarget::Cell(next_pos) if self.is_gass(next_pos) => fall_pos = next_pos,
+                MoveTarget::Void => {
+                    self.set_cell(pos, Cell::default_air());
+                    return true;
+                },
+                _ => break,
+            }
+        }
+        if fall_pos == pos {
+            self.land(pos, dirs.down);
+            return false;
+        }
+        let cell_type = self.cells[pos].cell_type;
+        self.cells[pos].fall_speed = speed;
+        let lands = !matches!(self.move_target(fall_pos + dirs.down), MoveTarget::Cell(below_pos) if self.is_gass(below_pos));
+        if lands {
+            self.cells[pos].fall_speed = speed.min(self.below_fall_speed(fall_pos, dirs.down));
+            // the landed cell may weigh on a static solid now
+            self.chunks.mark_solids_changed();
+        }
+        self.swap_cells(pos, fall_pos);
+        if lands && self.cells[fall_pos].cell_type == cell_type {
+            self.impact(fall_pos, speed, dirs.down);
+        }
+        true
+    }
+
+    // a falling cell slows down to the speed of what it lands on, returns whether the landing set it off
+    fn land(&mut self, pos: IVec2, down: IVec2) -> bool {
+        let speed = self.cells[pos].fall_speed;
+        self.cells[pos].fall_speed = speed.min(self.below_fall_speed(pos, down));
+        self.impact(pos, speed, down)
+    }
+
+    // explosives sensitive to shocks go off when they hit something hard fast enough
+    fn impact(&mut self, pos: IVec2, speed: f32, down: IVec2) -> bool {
+        let Some(explosive) = self.materials.explosive(self.cells[pos].cell_type) else {
+            return false;
+        };
+        let Detonation::Impact(min_speed) = explosive.detonation else {
+            return false;
+        };
+        let is_hard = match self.move_target(pos + down) {
+            MoveTarget::Cell(below_pos) => self.is_solid(below_pos),
+            MoveTarget::Blocked => true,
+            MoveTarget::Void => false,
+        };
+        if is_hard && speed >= min_speed {
+            self.detonate(pos, explosive);
+            return true;
+        }
+        false
+    }
+
+    // the explosive turns into a flame, its blast is applied after the chunk passes
+    // the flame gets no pressure of its own, which could set off a second blast as a gass pocket
+    fn detonate(&mut self, pos: IVec2, explosive: Explosive) {
+        self.replace_cell(pos, CellType::Fire);
+        let mut is_gass_neirby = false;
+        self.ignite_neighborhood(pos, &mut is_gass_neirby);
+        self.blasts.lock().unwrap().push(Blast { pos, radius: explosive.radius, power: explosive.power, from_pocket: false });
+    }
+
+    fn below_fall_speed(&self, pos: IVec2, down: IVec2) -> f32 {
+        match self.move_target(pos + down) {
+            MoveTarget::Cell(below_pos) => self.cells[below_pos].fall_speed,
+            _ => 0.0,
         }
-        return;
     }
 
     fn update_liquid(&mut self, pos: IVec2)
@@ -324,101 +1004,97 @@ impl CellGrid
     fn update_fluid(&mut self, pos: IVec2, is_liquid: bool)
     {
         let fluid_type = self.cells[pos].cell_type;
-        let move_dir = if is_liquid { -1 } else { 1 };
         // gass movement speed
-        if !is_liquid && rand::rng().random::<f32>() > self.cell_properties[fluid_type].movement_prob {
+        if !is_liquid && self.rng.random::<f32>() > self.materials[fluid_type].movement_prob {
             return;
         }
+        // for gasses down is against the gravity
+        let Some(dirs) = self.gravity_dirs(pos, is_liquid) else {
+            return;
+        };
         // vertical
-        let vert_pos = pos + IVec2::new(0, move_dir);
-        if self.cells.is_in_range(vert_pos) {
-            if !self.cells[vert_pos].is_solid() && self.compare_densities(self.cells[vert_pos].cell_type, fluid_type, is_liquid) {
-                // liquid fall speed
-                if is_liquid && rand::rng().random::<f32>() > self.liquid_fall_prob {
-                    return;
-                }
-                if self.rand_fallthrough(vert_pos) {
-                    self.swap_cells(pos, vert_pos);
-                }
+        let vert = self.move_target(pos + dirs.down);
+        if self.fluid_can_move_into(vert, fluid_type, is_liquid) {
+            // liquid fall speed
+            if is_liquid && self.rng.random::<f32>() > self.settings.liquid_fall_prob {
                 return;
             }
-        } else if !is_liquid && self.top_gass_leak {
-            self.cells[pos] = Cell::default_air();
+            if is_liquid && self.fall(pos, dirs) {
+                return;
+            }
+            match vert {
+                MoveTarget::Cell(vert_pos) => if self.rand_fallthrough(vert_pos) {
+                    self.swap_cells(pos, vert_pos);
+                },
+                _ => self.move_cell(pos, vert),
+            }
+            return;
+        }
+        if is_liquid && self.land(pos, dirs.down) {
             return;
         }
         // liquid movement speed
-        if is_liquid && rand::rng().random::<f32>() > self.cell_properties[fluid_type].movement_prob {
+        if is_liquid && self.rng.random::<f32>() > self.materials[fluid_type].movement_prob {
             return;
         }
-        // diagonal
-        let diag_left_pos = pos + IVec2::new(-1, move_dir);
-        let diag_right_pos = pos + IVec2::new(1, move_dir);
-        let choose = rand::rng().random_range(0..2);
-        let side_pos = [diag_left_pos, diag_right_pos];
-        if self.cells.is_in_range(side_pos[choose]) && !self.cells[side_pos[choose]].is_solid() && self.compare_densities(self.cells[side_pos[choose]].cell_type, fluid_type, is_liquid) {
-            if self.rand_fallthrough(side_pos[choose]) {
-                self.swap_cells(pos, side_pos[choose]);
-            }
+        // thick liquids rarely flow to the sides
+        let viscosity = self.materials[fluid_type].viscosity;
+        if viscosity > 0.0 && self.rng.random::<f32>() < viscosity {
             return;
-        } else if self.cells.is_in_range(side_pos[1 - choose]) && !self.cells[side_pos[1 - choose]].is_solid() && self.compare_densities(self.cells[side_pos[1 - choose]].cell_type, fluid_type, is_liquid) {
-            if self.rand_fallthrough(side_pos[1 - choose]) {
-                self.swap_cells(pos, side_pos[1 - choose]);
+        }
+        // diagonal
+        let choose = self.rng.random_range(0..2);
+        for diag_dir in [dirs.diagonals[choose], dirs.diagonals[1 - choose]] {
+            let diag = self.move_target(pos + diag_dir);
+            if self.fluid_can_move_into(diag, fluid_type, is_liquid) {
+                if let MoveTarget::Cell(diag_pos) = diag {
+                    if !self.rand_fallthrough(diag_pos) {
+                        return;
+                    }
+                }
+                self.move_cell(pos, diag);
+                return;
             }
-            return;
         }
         // sides
         if !self.cells[pos].does_fluid_slide() {
-            self.cells[pos].gen_fluid_slide_dir();
+            self.cells[pos].gen_fluid_slide_dir(self.rng);
         }
         let side_dir = self.cells[pos].get_fluid_slide_dir();
-        let side_pos1 = pos + IVec2::new(side_dir, 0);
-        let side_pos2 = pos + IVec2::new(-side_dir, 0);
-        if self.cells.is_in_range(side_pos1) && !self.cells[side_pos1].is_solid() && self.compare_densities(self.cells[side_pos1].cell_type, fluid_type, is_liquid) {
-            self.swap_cells(pos, side_pos1);
+        let dispersion = (self.materials[fluid_type].dispersion as i32).clamp(1, MAX_CELL_MOVE);
+        if let Some(side1) = self.find_slide_target(pos, dirs.side(side_dir), dispersion, dirs.down, is_liquid) {
+            self.move_cell(pos, side1);
             return;
         }
-        if self.cells.is_in_range(side_pos2)&& !self.cells[side_pos2].is_solid() && self.compare_densities(self.cells[side_pos2].cell_type, fluid_type, is_liquid) {
+        if let Some(side2) = self.find_slide_target(pos, dirs.side(-side_dir), dispersion, dirs.down, is_liquid) {
             self.cells[pos].reverse_fluid_slide_dir();
-            self.swap_cells(pos, side_pos2);
+            self.move_cell(pos, side2);
             return;
         }
         self.cells[pos].stop_fluid_slide();
-        return;
     }
 
-    fn update_acid(&mut self, pos: IVec2) {
-        if rand::rng().random::<f32>() > self.acid_reaction_prob {
-            return;
-        }
-        let down_pos = pos + IVec2::new(0, -1);
-        // neutralize with water
-        if rand::rng().random::<f32>() < self.neutralize_acid_prob {
-            if self.cells.is_in_range(down_pos) && self.cells[down_pos].cell_type == CellType::Water {
-                self.cells[pos] = self.new_cell(CellType::Water, pos);
-                return;
+    // the farthest cell the fluid can slide to in one update, it stops above the first gap it can fall into
+    fn find_slide_target(&self, pos: IVec2, side: IVec2, dispersion: i32, down: IVec2, is_liquid: bool) -> Option<MoveTarget>
+    {
+        let fluid_type = self.cells[pos].cell_type;
+        let mut found = None;
+        let mut slide_pos = pos;
+        for _ in 0..dispersion {
+            let target = self.move_target(slide_pos + side);
+            if !self.fluid_can_move_into(target, fluid_type, is_liquid) {
+                break;
             }
-        }
-        // dissolve materials
-        let left_pos = pos + IVec2::new(-1, 0);
-        let right_pos = pos + IVec2::new(1, 0);
-        let diag_left_pos = pos + IVec2::new(-1, -1);
-        let diag_right_pos = pos + IVec2::new(1, -1);
-        let side_pos = [down_pos, left_pos, right_pos, diag_left_pos, diag_right_pos];
-        let choose_pos = side_pos[rand::rng().random_range(0..side_pos.len())];
-        if self.cells.is_in_range(choose_pos) && self.cells[choose_pos].is_dissolvable() {
-            if self.cells[choose_pos].cell_type == CellType::Water {
-                self.cells[pos] = self.new_cell(CellType::Smoke, pos);
-            } else if self.cells[choose_pos].cell_type == CellType::Ice {
-                self.cells[pos] = self.new_cell(CellType::Smoke, pos);
-                self.cells[choose_pos] = self.new_cell(CellType::Water, choose_pos);
-            } else if self.cells[choose_pos].cell_type == CellType::Lava {
-                self.cells[pos] = self.new_cell(CellType::FlammableGass, pos);
-                self.cells[choose_pos] = self.new_cell(CellType::Fire, choose_pos);
-            } else {
-                self.cells[pos] = self.new_cell(CellType::FlammableGass, pos);
-                self.cells[choose_pos] = Cell::default_air();
+            found = Some(target);
+            let MoveTarget::Cell(next_pos) = target else {
+                break;
+            };
+            slide_pos = next_pos;
+            if self.fluid_can_move_into(self.move_target(slide_pos + down), fluid_type, is_liquid) {
+                break;
             }
         }
+        found
     }
 
     fn ignite_neighborhood(&mut self, pos: IVec2, is_gass_neirby: &mut bool)
@@ -429,31 +1105,21 @@ impl CellGrid
                 if x == 0 && y == 0 || !self.cells.is_in_range(ignite_pos) {
                     continue;
                 }
-                if self.cells[ignite_pos].is_gass() {
+                if self.is_gass(ignite_pos) {
                     *is_gass_neirby = true;
                 }
                 if self.cells[ignite_pos].is_on_fire() {
                     continue;
                 }
                 let cell_type = self.cells[ignite_pos].cell_type;
-                if rand::rng().random::<f32>() > self.cell_properties[cell_type].ignite_prob {
+                if self.rng.random::<f32>() > self.materials[cell_type].ignite_prob {
                     continue;
                 }
-                match cell_type {
-                    CellType::Air => {
-                        if self.cells[pos].cell_type != CellType::Fire && self.cells[pos].cell_type != CellType::Lava {
-                            self.cells[ignite_pos] = self.new_cell(CellType::Fire, ignite_pos);
-                        }
-                    },
-                    CellType::Water => {
-                        self.cells[ignite_pos] = self.new_cell(CellType::Steam, ignite_pos);
-                    },
-                    CellType::Ice => {
-                        self.cells[ignite_pos] = self.new_cell(CellType::Water, ignite_pos);
-                    },
-                    _ => {
-                        self.cells[ignite_pos].ignite();
-                    },
+                // materials catch fire from the heat, here the burning cell only spreads flames by the ignition rules
+                if let Some(rule) = self.reactions.find_ignition(self.materials, &self.cells[pos], &self.cells[ignite_pos]) {
+                    if self.rng.random::<f32>() < rule.prob {
+                        self.apply_reaction(rule, pos, ignite_pos);
+                    }
                 }
             }
         }
@@ -464,48 +1130,86 @@ impl CellGrid
         let mut is_gass_neirby = false;
         if !self.cells[pos].was_ignited_this_frame() {
             // change color
-            if rand::rng().random::<f32>() < self.cell_properties[CellType::Fire].color_change_prob {
-                if rand::rng().random::<f32>() < self.cell_properties[cell_type].fire_color_prob {
+            if self.rng.random::<f32>() < self.materials[CellType::Fire].color_change_prob {
+                if self.rng.random::<f32>() < self.materials[cell_type].fire_color_prob {
                     self.cells[pos].use_fire_color();
-                    self.cells[pos].color_offset = self.cell_properties[CellType::Fire].gen_color_offset(pos);
+                    self.cells[pos].color_offset = self.materials[CellType::Fire].gen_color_offset(pos, self.rng);
                 } else {
                     self.cells[pos].dont_use_fire_color();
-                    let shift = (-63.0* (1.0 - (self.cells[pos].get_timer() as f32) / (self.cell_properties[cell_type].timer as f32))) as i8;
-                    self.cells[pos].color_offset = self.cell_properties[CellType::Fire].gen_color_offset_shifted(pos, shift);
+                    let shift = (-63.0* (1.0 - (self.cells[pos].get_timer() as f32) / (self.materials[cell_type].timer as f32))) as i8;
+                    self.cells[pos].color_offset = self.materials[CellType::Fire].gen_color_offset_shifted(pos, shift, self.rng);
                 }
             }
             self.ignite_neighborhood(pos, &mut is_gass_neirby);
         } else {
             is_gass_neirby = true;
         }
-        // extinguish solids without gass neirby
-        if !self.cells[pos].is_gass() && !is_gass_neirby && rand::rng().random::<f32>() < self.fire_solid_extinguish_prob {
+        // extinguish solids without gass neirby, a lit explosive doesn't go out
+        let explosive = self.materials.explosive(cell_type);
+        if !self.is_gass(pos) && !is_gass_neirby && explosive.is_none() && self.rng.random::<f32>() < self.settings.fire_solid_extinguish_prob {
             self.cells[pos].extinguish();
             return;
         }
+        // burning gasses expand
+        if self.is_gass(pos) {
+            self.cells[pos].pressure += self.materials[cell_type].burn_pressure;
+        }
         // decrease fire
-        if rand::rng().random::<f32>() > self.fire_decrease_prob {
+        if self.rng.random::<f32>() > self.settings.fire_decrease_prob {
             return;
         }
         let mut flame_timer = self.cells[pos].get_timer() as i16;
         flame_timer -= 1;
         if flame_timer <= 0 {
-            if self.cell_properties[cell_type].smoke_after_burnout {
-                self.cells[pos] = self.new_cell(CellType::Smoke, pos);
+            if let Some(explosive) = explosive {
+                self.detonate(pos, explosive);
+                return;
+            }
+            // the combustion products keep the pressure
+            let pressure = self.cells[pos].pressure;
+            if self.materials[cell_type].smoke_after_burnout {
+                self.replace_cell(pos, CellType::Smoke);
             } else {
-                self.cells[pos] = self.new_cell(CellType::Air, pos);
+                self.replace_cell(pos, CellType::Air);
             }
+            self.cells[pos].pressure = pressure;
         } else {
             self.cells[pos].set_timer(flame_timer as u16);
         }
     }
 
+    // gasses even out the pressure with the neighboring gasses and slowly release it
+    fn update_pressure(&mut self, pos: IVec2) {
+        if self.cells[pos].pressure < SETTLED_PRESSURE {
+            self.cells[pos].pressure = 0.0;
+            return;
+        }
+        self.cells[pos].pressure *= 1.0 - self.settings.pressure_release;
+        for off in HEAT_OFFSETS {
+            let neighbor_pos = pos + off;
+            if !self.cells.is_in_range(neighbor_pos) || !self.is_gass(neighbor_pos) {
+                continue;
+            }
+            let flow = 0.25 * (self.cells[pos].pressure - self.cells[neighbor_pos].pressure);
+            if flow > 0.0 {
+                self.cells[pos].pressure -= flow;
+                self.cells[neighbor_pos].pressure += flow;
+                self.chunks.wake_rect(neighbor_pos, neighbor_pos);
+            }
+        }
+        let pressure = self.cells[pos].pressure;
+        if pressure >= self.settings.blast_pressure {
+            let radius = (pressure * self.settings.blast_radius_per_pressure).min(self.settings.max_blast_radius);
+            self.blasts.lock().unwrap().push(Blast { pos, radius, power: pressure, from_pocket: true });
+        }
+    }
+
     fn update_smoke(&mut self, pos: IVec2) {
-        if rand::rng().random::<f32>() < self.smoke_degradation_prob {
-            self.cells[pos] = Cell::default_air();
+        if self.rng.random::<f32>() < self.settings.smoke_degradation_prob {
+            self.set_cell(pos, Cell::default_air());
             return;
         }
-        if rand::rng().random::<f32>() > self.smoke_decrease_prob {
+        if self.rng.random::<f32>() > self.settings.smoke_decrease_prob {
             return;
         }
         let mut smoke_timer = self.cells[pos].get_timer() as i16;
@@ -515,82 +1219,92 @@ impl CellGrid
         }
         self.cells[pos].set_timer(smoke_timer as u16);
     }
+}
 
-    fn update_ice(&mut self, pos: IVec2) {
-        let mut offs = [
-            IVec2::new(-1, -1),
-            IVec2::new( 0, -1),
-            IVec2::new( 1, -1),
-            IVec2::new(-1,  0),
-            IVec2::new( 1,  0),
-            IVec2::new(-1,  1),
-            IVec2::new( 0,  1),
-            IVec2::new( 1,  1),
-        ];
-        offs.shuffle(&mut rand::rng());
-        let mut air_neirby = false;
-        for off in offs {
-            let freeze_pos = pos + off;
-            if !self.cells.is_in_range(freeze_pos) {
-                continue;
-            }
-            if self.cells[freeze_pos].cell_type == CellType::Air {
-                air_neirby = true;
-            }
-            let multiplier = if air_neirby { 10.0 } else { 1.0 };
-            if rand::rng().random::<f32>() > multiplier * self.freeze_prob {
-                continue;
-            }
-            if self.cells[freeze_pos].cell_type == CellType::Water {
-                self.cells[freeze_pos] = self.new_cell(CellType::Ice, freeze_pos);
-            }
-        }
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid_config::*;
 
-    fn update_lava(&mut self, pos: IVec2) {
-        if rand::rng().random::<f32>() > self.lava_ignite_prob {
-            return;
-        }
-        self.ignite_neighborhood(pos, &mut false);
+    const SIZES: IVec2 = IVec2::new(128, 96);
+
+    // a bit of everything, so most of the update rules get to run
+    fn test_grid(seed: u64) -> CellGrid
+    {
+        let mut grid = get_default_cell_grid(SIZES, seed);
+        grid.set_cells(IVec2::new(64, 70), None, BrushType::Circle, 12, CellType::Sand, false);
+        grid.set_cells(IVec2::new(32, 70), None, BrushType::Circle, 12, CellType::Water, false);
+        grid.set_cells(IVec2::new(96, 70), None, BrushType::Circle, 10, CellType::Lava, false);
+        grid.set_cells(IVec2::new(96, 20), None, BrushType::Square, 6, CellType::Wood, false);
+        grid.set_cells(IVec2::new(32, 40), None, BrushType::Circle, 6, CellType::Fire, false);
+        grid
     }
 
-    fn update_steam(&mut self, pos: IVec2) {
-        let up_pos = pos + IVec2::new(0, 1);
-        let in_range = self.cells.is_in_range(up_pos);
-        if in_range && self.cells[up_pos].cell_type == CellType::Ice {
-            if rand::rng().random::<f32>() > 3.0 * self.steam_liquify_prob {
-                return;
-            }
-            self.cells[pos] = self.new_cell(CellType::Water, pos);
-        }
-        if rand::rng().random::<f32>() > self.steam_liquify_prob {
-            return;
+    // brush strokes in the middle of the run, like the user drawing
+    fn draw(grid: &mut CellGrid, update: u32)
+    {
+        if update == 100 {
+            grid.set_cells(IVec2::new(20, 80), Some(IVec2::new(100, 80)), BrushType::LineRound, 2, CellType::Stone, true);
         }
-        if in_range && self.cells[up_pos].is_solid() || !in_range && !self.top_gass_leak {
-            self.cells[pos] = self.new_cell(CellType::Water, pos);
+        if update == 150 {
+            grid.set_cells(IVec2::new(60, 60), Some(IVec2::new(70, 50)), BrushType::Fling, 4, CellType::Air, false);
         }
     }
 
-    fn update_water(&mut self, pos: IVec2) {
-        if rand::rng().random::<f32>() > self.lava_cooldown_prob {
-            return;
-        }
-        let mut ys = [-1, 0, 1];
-        let mut xs = [-1, 0, 1];
-        ys.shuffle(&mut rand::rng());
-        xs.shuffle(&mut rand::rng());
-        for y in ys {
-            for x in xs {
-                let lava_pos = pos + IVec2::new(x, y);
-                if x == 0 && y == 0 || !self.cells.is_in_range(lava_pos) {
-                    continue;
-                }
-                if self.cells[lava_pos].cell_type == CellType::Lava {
-                    self.cells[pos] = self.new_cell(CellType::Stone, pos);
-                    self.cells[lava_pos] = self.new_cell(CellType::Stone, lava_pos);
-                    break;
-                }
-            }
+    fn run(grid: &mut CellGrid, updates: u32, mut on_frame: impl FnMut(u32, &CellGrid))
+    {
+        for update in 0..updates {
+            draw(grid, update);
+            grid.update(update % 2 == 0);
+            on_frame(update, grid);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn same_seed_gives_same_frames()
+    {
+        let mut frames = Vec::new();
+        run(&mut test_grid(7), 400, |_, grid| frames.push(grid.cells.data.clone()));
+        let mut other = test_grid(7);
+        run(&mut other, 400, |update, grid| {
+            assert!(grid.cells.data == frames[update as usize], "frames differ at update {update}");
+        });
+    }
+
+    #[test]
+    fn different_seeds_give_different_frames()
+    {
+        let mut grid = test_grid(7);
+        let mut other = test_grid(8);
+        run(&mut grid, 100, |_, _| {});
+        run(&mut other, 100, |_, _| {});
+        assert!(grid.cells.data != other.cells.data);
+    }
+
+    // every chunk draws from its own generator, so the threads can't change the outcome
+    // without the parallel feature both runs would be serial
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_update_matches_serial()
+    {
+        let mut serial = test_grid(7);
+        serial.parallel_update = false;
+        let mut parallel = test_grid(7);
+        parallel.parallel_update = true;
+        run(&mut serial, 400, |_, _| {});
+        run(&mut parallel, 400, |_, _| {});
+        assert!(serial.cells.data == parallel.cells.data);
+    }
+
+    // the flame left by the explosive mustn't go off again as a gass pocket
+    #[test]
+    fn detonated_explosive_blasts_once()
+    {
+        let mut grid = get_default_cell_grid(IVec2::new(64, 64), 7);
+        let tnt = grid.materials.find("TNT").unwrap();
+        grid.set_cells(IVec2::new(32, 32), None, BrushType::Square, 0, tnt, true);
+        grid.cells[IVec2::new(32, 32)].temperature = 400.0;
+        run(&mut grid, 200, |_, _| {});
+        assert_eq!(grid.blasts_fired, 1);
+    }
+}
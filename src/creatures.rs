@@ -0,0 +1,214 @@
+use bevy_math::prelude::*;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::cell::*;
+use crate::cell_grid::*;
+use crate::materials::*;
+use crate::reactions::*;
+
+// the lowest bit of the custom data of a creature cell is its heading, the rest its age
+const HEADING_RIGHT: u8 = 1;
+const AGE_SHIFT: u8 = 1;
+const MAX_AGE: u8 = u8::MAX >> AGE_SHIFT;
+// updates per unit of age
+const AGE_UPDATES: u64 = 64;
+// chance per move that a creature turns around on its own
+const TURN_PROB: f32 = 0.01;
+
+// how a creature moves, as written in data files
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Behavior {
+    // walks along the surface of solids, climbs steps one cell high and digs through the material
+    Walk { dig: String, dig_prob: f32 },
+    // swims through the liquid, out of it the creature suffocates
+    Swim { medium: String, suffocate_prob: f32 },
+}
+
+// moving agent living in the grid, its heading and age travel with the cell
+// it is pushed around by the rest of the simulation according to its phase and reacts like any other cell,
+// so the creatures drown, burn and dissolve through the reactions
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Creature
+{
+    pub behavior: Behavior,
+    // chance to move per update
+    pub move_prob: f32,
+    // age the creature dies of, in units of AGE_UPDATES updates
+    pub lifespan: u8,
+    // material the creature turns into when it dies
+    pub remains: String,
+}
+
+// behavior with the material names resolved
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BehaviorRule {
+    Walk { dig: CellType, dig_prob: f32 },
+    Swim { medium: CellType, suffocate_prob: f32 },
+}
+
+// creature with the material names resolved
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CreatureRule
+{
+    pub behavior: BehaviorRule,
+    pub move_prob: f32,
+    pub lifespan: u8,
+    pub remains: CellType,
+}
+
+impl MaterialRegistry {
+    // has to run after all the materials are registered, like the phase changes
+    pub fn resolve_creatures(&mut self) -> Result<(), String> {
+        let mut rules = Vec::with_capacity(self.len());
+        for material in self.materials.iter() {
+            let Some(creature) = &material.creature else {
+                rules.push(None);
+                continue;
+            };
+            let find = |name: &String| self.find(name).ok_or(format!("creature {} refers to unknown material {}", material.name, name));
+            let behavior = match &creature.behavior {
+                Behavior::Walk { dig, dig_prob } => BehaviorRule::Walk { dig: find(dig)?, dig_prob: *dig_prob },
+                Behavior::Swim { medium, suffocate_prob } => BehaviorRule::Swim { medium: find(medium)?, suffocate_prob: *suffocate_prob },
+            };
+            rules.push(Some(CreatureRule {
+                behavior,
+                move_prob: creature.move_prob,
+                lifespan: creature.lifespan.min(MAX_AGE),
+                remains: find(&creature.remains)?,
+            }));
+        }
+        for (material, rule) in self.materials.iter_mut().zip(rules) {
+            material.creature_rule = rule;
+        }
+        Ok(())
+    }
+}
+
+impl CellGrid
+{
+    // the creatures move after the chunk passes, every one of them at most once per update
+    // only the woken cells are searched, a living creature keeps its cell awake
+    pub fn update_creatures(&mut self)
+    {
+        let mut creatures = Vec::new();
+        for rect in self.chunks.current_rects() {
+            for y in rect.min.y..=rect.max.y {
+                for x in rect.min.x..=rect.max.x {
+                    let pos = IVec2::new(x, y);
+                    if let Some(rule) = self.materials.creature(self.cells[pos].cell_type) {
+                        creatures.push((pos, rule));
+                    }
+                }
+            }
+        }
+        let ages = self.updates.is_multiple_of(AGE_UPDATES);
+        for (pos, rule) in creatures {
+            self.chunks.wake_rect(pos, pos);
+            // the creatures only move into the cells the others have already left, so none of them moves twice
+            if self.cells[pos].is_on_fire() {
+                continue;
+            }
+            let data = self.cells[pos].custom_data;
+            let mut age = data >> AGE_SHIFT;
+            // a new creature picks its heading first
+            if age == 0 {
+                let heading = if self.rng.random::<bool>() { HEADING_RIGHT } else { 0 };
+                self.cells[pos].custom_data = heading | (1 << AGE_SHIFT);
+                continue;
+            }
+            if ages {
+                age += 1;
+                if age > rule.lifespan {
+                    self.replace_cell(pos, rule.remains);
+                    continue;
+                }
+                self.cells[pos].custom_data = (data & HEADING_RIGHT) | (age << AGE_SHIFT);
+            }
+            match rule.behavior {
+                BehaviorRule::Walk { dig, dig_prob } => self.walk(pos, rule, dig, dig_prob),
+                BehaviorRule::Swim { medium, suffocate_prob } => self.swim(pos, rule, medium, suffocate_prob),
+            }
+        }
+    }
+
+    // heading as written for the default gravity, the moves turn it along with the local gravity
+    fn heading(&self, pos: IVec2) -> IVec2
+    {
+        if self.cells[pos].custom_data & HEADING_RIGHT != 0 { IVec2::X } else { IVec2::NEG_X }
+    }
+
+    fn turn_around(&mut self, pos: IVec2)
+    {
+        self.cells[pos].custom_data ^= HEADING_RIGHT;
+    }
+
+    fn move_creature(&mut self, from_pos: IVec2, to_pos: IVec2)
+    {
+        let creature = self.cells[from_pos];
+        self.cells[from_pos] = self.cells[to_pos];
+        self.cells[to_pos] = creature;
+        self.chunks.wake_cell(from_pos);
+        self.chunks.wake_cell(to_pos);
+    }
+
+    fn is_open(&self, pos: IVec2) -> bool
+    {
+        self.cells.is_in_range(pos) && self.materials.is_gass(self.cells[pos].cell_type)
+    }
+
+    fn walk(&mut self, pos: IVec2, rule: CreatureRule, dig: CellType, dig_prob: f32)
+    {
+        // falling is left to the powder update
+        let up = self.settings.gravity_turn_at(pos, IVec2::Y);
+        if self.is_open(pos - up) || self.rng.random::<f32>() >= rule.move_prob {
+            return;
+        }
+        if self.rng.random::<f32>() < TURN_PROB {
+            self.turn_around(pos);
+        }
+        let heading = self.heading(pos);
+        let ahead = pos + self.settings.gravity_turn_at(pos, heading);
+        // under a diagonal gravity the step is turned as a whole to stay next to the creature
+        let step_up = pos + self.settings.gravity_turn_at(pos, heading + IVec2::Y);
+        if self.is_open(ahead) {
+            self.move_creature(pos, ahead);
+        } else if self.is_open(pos + up) && self.is_open(step_up) {
+            self.move_creature(pos, step_up);
+        } else if self.cells.is_in_range(ahead) && self.cells[ahead].cell_type == dig {
+            // the dug out cell is left behind the creature
+            if self.rng.random::<f32>() < dig_prob {
+                self.move_creature(pos, ahead);
+            }
+        } else {
+            self.turn_around(pos);
+        }
+    }
+
+    fn swim(&mut self, pos: IVec2, rule: CreatureRule, medium: CellType, suffocate_prob: f32)
+    {
+        let in_medium = Neighborhood::VonNeumann.offsets().iter().any(|off| {
+            let neighbor_pos = pos + *off;
+            self.cells.is_in_range(neighbor_pos) && self.cells[neighbor_pos].cell_type == medium
+        });
+        if !in_medium {
+            if self.rng.random::<f32>() < suffocate_prob {
+                self.replace_cell(pos, rule.remains);
+            }
+            return;
+        }
+        if self.rng.random::<f32>() >= rule.move_prob {
+            return;
+        }
+        if self.rng.random::<f32>() < TURN_PROB {
+            self.turn_around(pos);
+        }
+        let dir = self.heading(pos) + IVec2::new(0, self.rng.random_range(-1..=1));
+        let ahead = pos + self.settings.gravity_turn_at(pos, dir);
+        if self.cells.is_in_range(ahead) && self.cells[ahead].cell_type == medium {
+            self.move_creature(pos, ahead);
+        } else {
+            self.turn_around(pos);
+        }
+    }
+}
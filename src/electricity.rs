@@ -0,0 +1,110 @@
+use bevy_math::prelude::*;
+
+use crate::cell::*;
+use crate::cell_grid::*;
+use crate::reactions::*;
+
+// charge of a conductor cell the current has just reached, only such cells pass it on
+// it then runs down by one every update and the cell can't be charged again until it's empty,
+// so the current travels along the conductors in pulses and never flows back
+pub const FULL_CHARGE: u8 = 3;
+
+impl CellGrid
+{
+    // power sources and fully charged conductors charge the conductors next to them,
+    // the live conductors heat up the cells they touch and set flammable fluids on fire
+    // only the cells woken for this frame are scanned, the ones the current still passes through keep themselves awake
+    pub fn update_electricity(&mut self)
+    {
+        if !self.settings.electricity {
+            return;
+        }
+        self.update_open_doors();
+        let rects = self.chunks.current_rects();
+        let mut charged = Vec::new();
+        for rect in rects.iter() {
+            for y in rect.min.y..=rect.max.y {
+                for x in rect.min.x..=rect.max.x {
+                    self.update_electric_cell(IVec2::new(x, y), &mut charged);
+                }
+            }
+        }
+        for rect in rects.iter() {
+            for y in rect.min.y..=rect.max.y {
+                for x in rect.min.x..=rect.max.x {
+                    let pos = IVec2::new(x, y);
+                    let cell = &mut self.cells[pos];
+                    cell.charge = cell.charge.saturating_sub(1);
+                    if cell.charge > 0 {
+                        self.chunks.wake_rect(pos, pos);
+                    }
+                }
+            }
+        }
+        for pos in charged {
+            self.cells[pos].charge = FULL_CHARGE;
+            self.chunks.wake_cell(pos);
+        }
+    }
+
+    fn update_electric_cell(&mut self, pos: IVec2, charged: &mut Vec<IVec2>)
+    {
+        let cell = self.cells[pos];
+        if let Some(rule) = self.materials.logic(cell.cell_type) {
+            self.update_logic_cell(pos, rule, charged);
+            return;
+        }
+        let is_source = self.materials.is_power_source(cell.cell_type);
+        let is_live = cell.charge == FULL_CHARGE && self.materials.is_conductive(cell.cell_type);
+        if !is_source && !is_live {
+            return;
+        }
+        // the current only passes through edges, so wires touching at a corner stay separate, like the gate inputs and outputs
+        let mut touches_conductor = false;
+        for off in Neighborhood::VonNeumann.offsets() {
+            let neighbor_pos = pos + *off;
+            if !self.cells.is_in_range(neighbor_pos) || !self.materials.is_conductive(self.cells[neighbor_pos].cell_type) {
+                continue;
+            }
+            touches_conductor = true;
+            if self.cells[neighbor_pos].charge == 0 {
+                charged.push(neighbor_pos);
+            }
+        }
+        // a source keeps charging its conductors again as soon as they run empty
+        if is_source && touches_conductor {
+            self.chunks.wake_rect(pos, pos);
+        }
+        if !is_live {
+            return;
+        }
+        for off in Neighborhood::Moore.offsets() {
+            let neighbor_pos = pos + *off;
+            if !self.cells.is_in_range(neighbor_pos) {
+                continue;
+            }
+            let neighbor_type = self.cells[neighbor_pos].cell_type;
+            if !self.materials.is_conductive(neighbor_type) && !self.materials.is_power_source(neighbor_type) && self.materials.logic(neighbor_type).is_none() {
+                self.electrify(neighbor_pos);
+            }
+        }
+    }
+
+    // the flammable gasses and liquids get a spark, the rest of the liquids and solids heats up
+    fn electrify(&mut self, pos: IVec2)
+    {
+        let cell_type = self.cells[pos].cell_type;
+        let properties = &self.materials[cell_type];
+        let phase = self.materials.phase(cell_type);
+        if properties.ignition_temperature.is_some() && !phase.is_solid() {
+            let spark_temperature = self.settings.spark_temperature;
+            self.cells[pos].temperature = self.cells[pos].temperature.max(spark_temperature);
+        } else if phase != CellPhase::Gass {
+            self.cells[pos].temperature += self.settings.electric_heat / properties.heat_capacity;
+        } else {
+            return;
+        }
+        // the cell catches fire or changes its phase in its own update
+        self.chunks.wake_cell(pos);
+    }
+}
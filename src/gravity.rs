@@ -0,0 +1,103 @@
+use std::f32::consts::FRAC_PI_4;
+
+use bevy_math::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::cell_grid::*;
+
+// neighbors counter-clockwise from the right, so the directions next to each other are neighbors in the ring too
+const DIRECTION_RING: [IVec2; 8] = [
+    IVec2::new(1, 0), IVec2::new(1, 1), IVec2::new(0, 1), IVec2::new(-1, 1),
+    IVec2::new(-1, 0), IVec2::new(-1, -1), IVec2::new(0, -1), IVec2::new(1, -1),
+];
+// weaker gravity doesn't move the cells at all
+const MIN_GRAVITY: f32 = 0.01;
+
+// pulls everything towards its position with the same strength at any distance, like a planet
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct GravityPoint
+{
+    pub pos: Vec2,
+    pub strength: f32,
+}
+
+// grid directions of the moves relative to the local gravity
+#[derive(Clone, Copy)]
+pub struct GravityDirs
+{
+    pub down: IVec2,
+    // the two directions next to down, the clockwise one first
+    pub diagonals: [IVec2; 2],
+    // the two directions perpendicular to down, the clockwise one first
+    pub sides: [IVec2; 2],
+    pub strength: f32,
+}
+
+impl GravityDirs {
+    // the gravity snapped to the closest of the eight neighbors, None without any gravity
+    pub fn new(gravity: Vec2) -> Option<Self>
+    {
+        let strength = gravity.length();
+        if strength < MIN_GRAVITY {
+            return None;
+        }
+        let down_index = (gravity.to_angle() / FRAC_PI_4).round() as i32;
+        let dir = |offset: i32| DIRECTION_RING[(down_index + offset).rem_euclid(8) as usize];
+        Some(GravityDirs { down: dir(0), diagonals: [dir(-1), dir(1)], sides: [dir(-2), dir(2)], strength })
+    }
+
+    // side of the fluid slide direction
+    pub fn side(&self, slide_dir: i32) -> IVec2 {
+        if slide_dir > 0 { self.sides[1] } else { self.sides[0] }
+    }
+
+    // the direction given for the default gravity, turned along with this gravity
+    pub fn turn(&self, dir: IVec2) -> IVec2 {
+        (self.sides[1] * dir.x - self.down * dir.y).signum()
+    }
+}
+
+impl CellGridSettings {
+    // the gravity at the position in cells, the uniform one combined with the pull of the gravity points
+    pub fn gravity_at(&self, pos: Vec2) -> Vec2
+    {
+        self.gravity + self.gravity_points.iter()
+            .map(|point| (point.pos - pos).normalize_or_zero() * point.strength)
+            .sum::<Vec2>()
+    }
+
+    // moves of the cell relative to the gravity at its center, None without any gravity
+    pub fn gravity_dirs_at(&self, pos: IVec2) -> Option<GravityDirs>
+    {
+        GravityDirs::new(self.gravity_at(pos.as_vec2() + 0.5))
+    }
+
+    // the direction given for the default gravity, turned along with the gravity at the cell
+    // the default directions are kept without any gravity
+    pub fn gravity_turn_at(&self, pos: IVec2, dir: IVec2) -> IVec2
+    {
+        self.gravity_dirs_at(pos).map_or(dir, |dirs| dirs.turn(dir))
+    }
+
+    // height of the cell center against the gravity, in cells under the default gravity
+    // the uniform gravity rises along its opposite direction, the pull of a point grows with the distance from it
+    pub fn gravity_height(&self, pos: IVec2) -> f32
+    {
+        let pos = pos.as_vec2() + 0.5;
+        -self.gravity.dot(pos) + self.gravity_points.iter()
+            .map(|point| point.pos.distance(pos) * point.strength)
+            .sum::<f32>()
+    }
+}
+
+impl CellGrid
+{
+    // settled cells may start moving in the new direction, so the whole grid is woken up
+    // and the solids are checked again for what holds them up now
+    pub fn set_gravity(&mut self, gravity: Vec2)
+    {
+        self.settings.gravity = gravity;
+        self.chunks.wake_all();
+        self.chunks.mark_solids_changed();
+    }
+}
@@ -0,0 +1,227 @@
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+
+use bevy_math::prelude::*;
+
+use crate::utils::*;
+
+pub const CHUNK_SIZE: i32 = 16;
+
+// inclusive rectangle of cells in grid coordinates, empty when min > max
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DirtyRect
+{
+    pub min: IVec2,
+    pub max: IVec2,
+}
+
+impl DirtyRect {
+    pub fn empty() -> Self {
+        DirtyRect { min: IVec2::MAX, max: IVec2::MIN }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y
+    }
+
+    pub fn contains_row(&self, y: i32) -> bool {
+        !self.is_empty() && y >= self.min.y && y <= self.max.y
+    }
+
+    pub fn include(&mut self, min: IVec2, max: IVec2) {
+        self.min = self.min.min(min);
+        self.max = self.max.max(max);
+    }
+}
+
+// dirty rectangle that can be grown from several threads at once
+pub struct AtomicDirtyRect
+{
+    min_x: AtomicI32,
+    min_y: AtomicI32,
+    max_x: AtomicI32,
+    max_y: AtomicI32,
+}
+
+impl AtomicDirtyRect {
+    pub fn new(rect: DirtyRect) -> Self {
+        AtomicDirtyRect {
+            min_x: AtomicI32::new(rect.min.x),
+            min_y: AtomicI32::new(rect.min.y),
+            max_x: AtomicI32::new(rect.max.x),
+            max_y: AtomicI32::new(rect.max.y),
+        }
+    }
+
+    pub fn load(&self) -> DirtyRect {
+        DirtyRect {
+            min: IVec2::new(self.min_x.load(Ordering::Relaxed), self.min_y.load(Ordering::Relaxed)),
+            max: IVec2::new(self.max_x.load(Ordering::Relaxed), self.max_y.load(Ordering::Relaxed)),
+        }
+    }
+
+    pub fn include(&self, min: IVec2, max: IVec2) {
+        // cells are mostly woken inside of already awake rectangles, reading is cheaper than writing
+        let rect = self.load();
+        if rect.min.x <= min.x && rect.min.y <= min.y && rect.max.x >= max.x && rect.max.y >= max.y {
+            return;
+        }
+        self.min_x.fetch_min(min.x, Ordering::Relaxed);
+        self.min_y.fetch_min(min.y, Ordering::Relaxed);
+        self.max_x.fetch_max(max.x, Ordering::Relaxed);
+        self.max_y.fetch_max(max.y, Ordering::Relaxed);
+    }
+}
+
+impl Clone for AtomicDirtyRect {
+    fn clone(&self) -> Self {
+        AtomicDirtyRect::new(self.load())
+    }
+}
+
+#[derive(Clone)]
+pub struct GridChunk
+{
+    // cells updated this frame
+    pub current: DirtyRect,
+    // cells that changed or are still active, updated next frame
+    pub next: AtomicDirtyRect,
+}
+
+impl GridChunk {
+    pub fn is_sleeping(&self) -> bool {
+        self.current.is_empty()
+    }
+}
+
+// the chunks far from the view are frozen in place rather than paged out to a compact storage,
+// they keep their full cells in the grid and only stop costing update time
+pub struct GridChunks
+{
+    pub chunks: Vector2D<GridChunk>,
+    pub grid_sizes: IVec2,
+    // chunks outside of the inclusive range are frozen, they keep their woken cells until they are active again
+    pub active_min: IVec2,
+    pub active_max: IVec2,
+    // a static solid or the load on one changed since the last structural integrity pass
+    solids_changed: AtomicBool,
+    // a static solid or what holds one up changed since the last search for rigid bodies
+    supports_changed: AtomicBool,
+}
+
+impl GridChunks {
+    pub fn new(grid_sizes: IVec2) -> Self {
+        let chunk_counts = (grid_sizes + CHUNK_SIZE - 1) / CHUNK_SIZE;
+        let chunk = GridChunk { current: DirtyRect::empty(), next: AtomicDirtyRect::new(DirtyRect::empty()) };
+        let chunks = GridChunks { chunks: Vector2D::new(chunk_counts, chunk), grid_sizes, active_min: IVec2::ZERO, active_max: chunk_counts - 1, solids_changed: AtomicBool::new(true), supports_changed: AtomicBool::new(true) };
+        chunks.wake_all();
+        chunks
+    }
+
+    pub fn sizes(&self) -> IVec2 {
+        self.chunks.sizes
+    }
+
+    pub fn count(&self) -> usize {
+        self.chunks.total_size()
+    }
+
+    pub fn chunk_min(&self, chunk_pos: IVec2) -> IVec2 {
+        chunk_pos * CHUNK_SIZE
+    }
+
+    pub fn chunk_max(&self, chunk_pos: IVec2) -> IVec2 {
+        ((chunk_pos + 1) * CHUNK_SIZE - 1).min(self.grid_sizes - 1)
+    }
+
+    // the cells in the rectangle get updated in the next frame
+    pub fn wake_rect(&self, min: IVec2, max: IVec2) {
+        let min = min.max(IVec2::ZERO);
+        let max = max.min(self.grid_sizes - 1);
+        if min.x > max.x || min.y > max.y {
+            return;
+        }
+        let chunk_from = min / CHUNK_SIZE;
+        let chunk_to = max / CHUNK_SIZE;
+        for chunk_y in chunk_from.y..=chunk_to.y {
+            for chunk_x in chunk_from.x..=chunk_to.x {
+                let chunk_pos = IVec2::new(chunk_x, chunk_y);
+                let rect_min = min.max(self.chunk_min(chunk_pos));
+                let rect_max = max.min(self.chunk_max(chunk_pos));
+                self.chunks[chunk_pos].next.include(rect_min, rect_max);
+            }
+        }
+    }
+
+    // wakes the cell together with its neighborhood, since those might react to its change
+    pub fn wake_cell(&self, pos: IVec2) {
+        self.wake_rect(pos - 1, pos + 1);
+    }
+
+    pub fn wake_all(&self) {
+        self.wake_rect(IVec2::ZERO, self.grid_sizes - 1);
+    }
+
+    // only the chunks touching the rectangle of cells get updated
+    pub fn set_active_rect(&mut self, min: IVec2, max: IVec2) {
+        self.active_min = (min / CHUNK_SIZE).max(IVec2::ZERO);
+        self.active_max = (max / CHUNK_SIZE).min(self.sizes() - 1);
+    }
+
+    // inclusive rectangle of the cells in the active chunks
+    pub fn active_cells_rect(&self) -> (IVec2, IVec2) {
+        (self.chunk_min(self.active_min), self.chunk_max(self.active_max))
+    }
+
+    pub fn is_active(&self, chunk_pos: IVec2) -> bool {
+        chunk_pos.x >= self.active_min.x && chunk_pos.y >= self.active_min.y
+            && chunk_pos.x <= self.active_max.x && chunk_pos.y <= self.active_max.y
+    }
+
+    // the cell lies in an active chunk
+    pub fn is_active_cell(&self, pos: IVec2) -> bool {
+        self.is_active(pos.div_euclid(IVec2::splat(CHUNK_SIZE)))
+    }
+
+    // moves the rectangles woken during the last frame into the current one
+    pub fn start_frame(&mut self) {
+        for i in 0..self.chunks.data.len() {
+            let active = self.is_active(self.chunks.index_to_vec(i));
+            let chunk = &mut self.chunks.data[i];
+            if !active {
+                chunk.current = DirtyRect::empty();
+                continue;
+            }
+            chunk.current = chunk.next.load();
+            chunk.next = AtomicDirtyRect::new(DirtyRect::empty());
+        }
+    }
+
+    pub fn mark_solids_changed(&self) {
+        self.solids_changed.store(true, Ordering::Relaxed);
+        self.mark_supports_changed();
+    }
+
+    // called for many moves from all the threads, so the flag is only written when it isn't set yet
+    pub fn mark_supports_changed(&self) {
+        if !self.supports_changed.load(Ordering::Relaxed) {
+            self.supports_changed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn take_supports_changed(&self) -> bool {
+        self.supports_changed.swap(false, Ordering::Relaxed)
+    }
+
+    pub fn take_solids_changed(&self) -> bool {
+        self.solids_changed.swap(false, Ordering::Relaxed)
+    }
+
+    pub fn current_rect(&self, chunk_pos: IVec2) -> DirtyRect {
+        self.chunks[chunk_pos].current
+    }
+
+    // the cells updated this frame in every chunk that isn't sleeping, the frozen chunks have none
+    pub fn current_rects(&self) -> Vec<DirtyRect> {
+        self.chunks.data.iter().map(|chunk| chunk.current).filter(|rect| !rect.is_empty()).collect()
+    }
+}
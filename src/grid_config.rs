@@ -1,229 +1,889 @@
-use bevy::prelude::*;
-use enum_map::enum_map;
+use std::path::Path;
 
-use crate::{cell::*, cell_grid::*, color_settings::*, utils::*};
+use bevy_math::prelude::*;
+use rand::prelude::*;
+use bevy_color::prelude::*;
+use serde::{Deserialize, Serialize};
 
-pub fn get_default_cell_grid(img_size: u32) -> CellGrid
+use crate::{boundaries::*, cell::*, cell_grid::*, color_settings::*, grid_chunks::*, liquid_pressure::*, rigid_bodies::*, logic::*, materials::*, plants::*, creatures::*, reactions::*, utils::*};
+
+// looked up in the assets directory
+pub const GRID_CONFIG_FILE: &str = "grid_config.ron";
+
+// everything tunable about the simulation, can be loaded from a data file
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GridConfig
 {
-    let colors = lospec500_palette();
+    pub settings: CellGridSettings,
+    // the built-in materials have to come first, in the order of BUILT_IN_MATERIALS
+    pub materials: Vec<Material>,
+    // names of the materials that get a button in the UI
+    pub buttons: Vec<String>,
+    // tried in order, so specific reactions have to come before general ones
+    pub reactions: Vec<Reaction>,
+}
+
+pub fn get_default_grid_config() -> GridConfig
+{
+    let materials = get_default_materials();
+    GridConfig {
+        settings: get_default_settings(),
+        buttons: materials.buttons.iter().map(|cell_type| materials.get(*cell_type).name.clone()).collect(),
+        materials: materials.materials,
+        reactions: get_default_reactions(),
+    }
+}
+
+pub fn load_grid_config(path: &Path) -> Result<GridConfig, String>
+{
+    let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    ron::from_str(&text).map_err(|err| err.to_string())
+}
+
+pub fn materials_from_config(config: &GridConfig) -> Result<MaterialRegistry, String>
+{
+    let mut materials = MaterialRegistry::new();
+    for material in &config.materials {
+        if materials.find(&material.name).is_some() {
+            return Err(format!("material {} is defined twice", material.name));
+        }
+        if materials.len() > u8::MAX as usize {
+            return Err(String::from("too many materials"));
+        }
+        // the heat conduction and the electric heating divide by it
+        if material.properties.heat_capacity <= 0.0 {
+            return Err(format!("material {} needs a positive heat capacity", material.name));
+        }
+        if let RandColorPattern::Stretched { amount, orig_prob, .. } = material.properties.rand_color_pattern {
+            if amount <= 0 || !(0.0..=1.0).contains(&orig_prob) {
+                return Err(format!("material {} needs a positive stretch amount and a probability of the original color between 0 and 1", material.name));
+            }
+        }
+        materials.register(material.clone());
+    }
+    for (cell_type, name) in BUILT_IN_MATERIALS {
+        if materials.find(name) != Some(cell_type) {
+            return Err(format!("built-in material {} has to be defined as material number {}", name, cell_type.0));
+        }
+    }
+    for name in &config.buttons {
+        let cell_type = materials.find(name).ok_or(format!("button for unknown material {}", name))?;
+        materials.add_button(cell_type);
+    }
+    materials.resolve_phase_changes()?;
+    materials.resolve_logic()?;
+    materials.resolve_plants()?;
+    materials.resolve_creatures()?;
+    Ok(materials)
+}
+
+pub fn cell_grid_from_config(config: &GridConfig, grid_sizes: IVec2, seed: u64) -> Result<CellGrid, String>
+{
+    let chunks = GridChunks::new(grid_sizes);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let chunk_rngs = gen_chunk_rngs(chunks.count(), &mut rng);
+    let materials = materials_from_config(config)?;
+    let reactions = ReactionTable::new(&config.reactions, &materials)?;
+    let mut settings = config.settings.clone();
+    settings.boundaries.resolve(&materials)?;
 
-    CellGrid {
-        top_gass_leak: true,
+    Ok(CellGrid {
+        settings,
+        cells: Vector2D::<Cell>::new(
+            grid_sizes,
+            Cell::default_air(),
+        ),
+        chunks,
+        particles: Vec::new(),
+        bodies: Vec::new(),
+        open_doors: Vec::new(),
+        liquid_pressure_state: LiquidPressureState::default(),
+        body_search_state: BodySearchState::default(),
+        updates: 0,
+        blasts_fired: 0,
+        materials,
+        reactions,
+        rng,
+        chunk_rngs,
+        parallel_update: true,
+    })
+}
+
+pub fn get_default_cell_grid(grid_sizes: IVec2, seed: u64) -> CellGrid
+{
+    cell_grid_from_config(&get_default_grid_config(), grid_sizes, seed).unwrap()
+}
+
+pub fn get_default_settings() -> CellGridSettings
+{
+    CellGridSettings {
+        boundaries: Boundaries {
+            left: Boundary::new(BoundaryType::Wall),
+            right: Boundary::new(BoundaryType::Wall),
+            bottom: Boundary::new(BoundaryType::Wall),
+            // gasses leak out of the top
+            top: Boundary::new(BoundaryType::Void),
+        },
+        rigid_bodies: true,
+        structural_integrity: false,
+        liquid_pressure: true,
+        electricity: true,
+        plants: true,
         powder_fall_prob: 0.95,
         powder_liquid_stuck_prob: 0.05,
         liquid_fall_prob: 0.9,
-        acid_reaction_prob: 0.05,
-        neutralize_acid_prob: 0.3,
-        steam_liquify_prob: 0.005,
-        freeze_prob: 0.0003,
-        lava_cooldown_prob: 0.01,
-        lava_ignite_prob: 0.7,
         fire_decrease_prob: 0.05,
         fire_solid_extinguish_prob: 0.1,
         smoke_decrease_prob: 0.2,
         smoke_degradation_prob: 0.003,
-        wood_flame_ash_prob: 0.1,
-        fire_color_prob: 0.9,
-        cells: Vector2D::<Cell>::new(
-            IVec2 { x: img_size as i32, y: img_size  as i32 },
-            Cell::default_air(),
-        ),
-        cell_properties: enum_map! {
-            CellType::Air => CellTypeProperties {
-                density: 0.5,
-                colors: colors[CellType::Air].clone(),
-                rand_color_pattern: RandColorPattern::None,
-                color_rand_radius: 0.0,
-                color_change_prob: 0.0,
-                movement_prob: 1.0,
-                fallthroug_prob: 1.0,
-                ignite_prob: 0.1,
-                timer: 0,
-                smoke_after_burnout: true,
-                fire_color_prob: 0.0,
-            },
-            CellType::Smoke => CellTypeProperties {
-                density: 0.2,
-                colors: colors[CellType::Smoke].clone(),
-                rand_color_pattern: RandColorPattern::None,
-                color_rand_radius: 0.25,
-                color_change_prob: 0.02,
-                movement_prob: 0.3,
-                fallthroug_prob: 1.0,
-                ignite_prob: 0.0,
-                timer: 31,
-                smoke_after_burnout: true,
-                fire_color_prob: 0.0,
-            },
-            CellType::FlammableGass => CellTypeProperties {
-                density: 0.3,
-                colors: colors[CellType::FlammableGass].clone(),
-                rand_color_pattern: RandColorPattern::None,
-                color_rand_radius: 0.25,
-                color_change_prob: 0.03,
-                movement_prob: 0.15,
-                fallthroug_prob: 1.0,
-                ignite_prob: 0.3,
-                timer: 4,
-                smoke_after_burnout: false,
-                fire_color_prob: 0.9,
-            },
-            CellType::Fire => CellTypeProperties {
-                density: 0.1,
-                colors: colors[CellType::Fire].clone(),
-                rand_color_pattern: RandColorPattern::None,
-                color_rand_radius: 0.25,
-                color_change_prob: 0.1,
-                movement_prob: 0.3,
-                fallthroug_prob: 1.0,
-                ignite_prob: 0.0,
-                timer: 2,
-                smoke_after_burnout: true,
-                fire_color_prob: 1.0,
-            },
-            CellType::Steam => CellTypeProperties {
-                density: 0.15,
-                colors: colors[CellType::Steam].clone(),
-                rand_color_pattern: RandColorPattern::None,
-                color_rand_radius: 0.25,
-                color_change_prob: 0.03,
-                movement_prob: 0.25,
-                fallthroug_prob: 1.0,
-                ignite_prob: 0.0,
-                timer: 0,
-                smoke_after_burnout: true,
-                fire_color_prob: 1.0,
-            },
-            CellType::Water => CellTypeProperties {
-                density: 2.0,
-                colors: colors[CellType::Water].clone(),
-                rand_color_pattern: RandColorPattern::None,
-                color_rand_radius: 0.02,
-                color_change_prob: 0.01,
-                movement_prob: 0.9,
-                fallthroug_prob: 0.3,
-                ignite_prob: 0.015,
-                timer: 0,
-                smoke_after_burnout: true,
-                fire_color_prob: 0.0,
-            },
-            CellType::Oil => CellTypeProperties {
-                density: 1.5,
-                colors: colors[CellType::Oil].clone(),
-                rand_color_pattern: RandColorPattern::None,
-                color_rand_radius: 0.02,
-                color_change_prob: 0.01,
-                movement_prob: 0.5,
-                fallthroug_prob: 0.3,
-                ignite_prob: 0.015,
-                timer: 15,
-                smoke_after_burnout: true,
-                fire_color_prob: 0.6,
-            },
-            CellType::Acid => CellTypeProperties {
-                density: 1.0,
-                colors: colors[CellType::Acid].clone(),
-                rand_color_pattern: RandColorPattern::None,
-                color_rand_radius: 0.1,
-                color_change_prob: 0.1,
-                movement_prob: 0.8,
-                fallthroug_prob: 0.3,
-                ignite_prob: 0.0,
-                timer: 0,
-                smoke_after_burnout: true,
-                fire_color_prob: 0.0,
-            },
-            CellType::Lava => CellTypeProperties {
-                density: 3.0,
-                colors: colors[CellType::Lava].clone(),
-                rand_color_pattern: RandColorPattern::None,
-                color_rand_radius: 0.25,
-                color_change_prob: 0.02,
-                movement_prob: 0.4,
-                fallthroug_prob: 0.3,
-                ignite_prob: 0.0,
-                timer: 0,
-                smoke_after_burnout: true,
-                fire_color_prob: 0.0,
-            },
-            CellType::Stone => CellTypeProperties {
-                density: 10.0,
-                colors: colors[CellType::Stone].clone(),
-                rand_color_pattern: RandColorPattern::Stretched { amount: 2, use_x: true, orig_prob: 0.1 },
-                color_rand_radius: 0.25,
-                color_change_prob: 0.0,
-                movement_prob: 1.0,
-                fallthroug_prob: 0.0,
-                ignite_prob: 0.0,
-                timer: 0,
-                smoke_after_burnout: true,
-                fire_color_prob: 0.0,
-            },
-            CellType::Wood => CellTypeProperties {
-                density: 10.0,
-                colors: colors[CellType::Wood].clone(),
-                rand_color_pattern: RandColorPattern::Stretched { amount: 2, use_x: false, orig_prob: 0.05 },
-                color_rand_radius: 0.25,
-                color_change_prob: 0.0,
-                movement_prob: 1.0,
-                fallthroug_prob: 0.0,
-                ignite_prob: 0.01,
-                timer: 10,
-                smoke_after_burnout: true,
-                fire_color_prob: 0.5,
-            },
-            CellType::Glass => CellTypeProperties {
-                density: 10.0,
-                colors: colors[CellType::Glass].clone(),
-                rand_color_pattern: RandColorPattern::Stretched { amount: 5, use_x: false, orig_prob: 0.01 },
-                color_rand_radius: 0.15,
-                color_change_prob: 0.0,
-                movement_prob: 1.0,
-                fallthroug_prob: 0.0,
-                ignite_prob: 0.0,
-                timer: 0,
-                smoke_after_burnout: true,
-                fire_color_prob: 0.0,
-            },
-            CellType::Ice => CellTypeProperties {
-                density: 10.0,
-                colors: colors[CellType::Ice].clone(),
-                rand_color_pattern: RandColorPattern::Stretched { amount: 3, use_x: true, orig_prob: 0.1 },
-                color_rand_radius: 0.15,
-                color_change_prob: 0.0,
-                movement_prob: 1.0,
-                fallthroug_prob: 0.0,
-                ignite_prob: 0.5,
-                timer: 0,
-                smoke_after_burnout: true,
-                fire_color_prob: 0.0,
-            },
-            CellType::Sand => CellTypeProperties {
-                density: 10.0,
-                colors: colors[CellType::Sand].clone(),
-                rand_color_pattern: RandColorPattern::None,
-                color_rand_radius: 0.25,
-                color_change_prob: 0.0,
-                movement_prob: 0.95,
-                fallthroug_prob: 0.0,
-                ignite_prob: 0.0,
-                timer: 0,
-                smoke_after_burnout: true,
-                fire_color_prob: 0.0,
-            },
-            CellType::Coal => CellTypeProperties {
-                density: 10.0,
-                colors: colors[CellType::Coal].clone(),
-                rand_color_pattern: RandColorPattern::None,
-                color_rand_radius: 0.25,
-                color_change_prob: 0.0,
-                movement_prob: 0.1,
-                fallthroug_prob: 0.0,
-                ignite_prob: 0.01,
-                timer: 20,
-                smoke_after_burnout: true,
-                fire_color_prob: 0.5,
-            },
-        }
+        air_heat_loss: 0.01,
+        pressure_release: 0.01,
+        blast_pressure: 50.0,
+        blast_radius_per_pressure: 0.15,
+        max_blast_radius: 12.0,
+        blast_push: 0.05,
+        gravity: Vec2::new(0.0, -1.0),
+        gravity_points: Vec::new(),
+        particle_gravity: 0.1,
+        max_particle_speed: 4.0,
+        splash_speed: 1.5,
+        fling_speed: 0.1,
+        body_check_interval: 8,
+        body_impact_strength: 5.0,
+        integrity_check_interval: 16,
+        integrity_reach_per_strength: 0.4,
+        integrity_load: 0.1,
+        liquid_pressure_interval: 2,
+        liquid_pressure_flow: 4,
+        jet_head: 8.0,
+        electric_heat: 40.0,
+        spark_temperature: 400.0,
+        plant_growth_prob: 0.5,
     }
-}
\ No newline at end of file
+}
+
+pub fn get_default_materials() -> MaterialRegistry
+{
+    let colors = lospec500_palette();
+    let mut materials = MaterialRegistry::new();
+    // the built-in materials have to end up with the ids of the CellType constants
+    let mut register = |cell_type: CellType, material: Material| {
+        assert_eq!(materials.register(material), cell_type);
+    };
+
+    register(CellType::Air, Material::new("Air", CellPhase::Gass, MaterialFlags::NONE, CellTypeProperties {
+        density: 0.5,
+        colors: colors[&CellType::Air].clone(),
+        rand_color_pattern: RandColorPattern::None,
+        color_rand_radius: 0.0,
+        color_change_prob: 0.0,
+        movement_prob: 1.0,
+        fallthroug_prob: 1.0,
+        viscosity: 0.0,
+        dispersion: 1,
+        max_fall_speed: 1.0,
+        ignite_prob: 0.1,
+        ignition_temperature: None,
+        conductivity: 0.02,
+        heat_capacity: 0.1,
+        temperature: 20.0,
+        strength: 0.0,
+        burn_pressure: 0.0,
+        timer: 0,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.0,
+    }).with_hotkey(0));
+    register(CellType::Smoke, Material::new("Smoke", CellPhase::Gass, MaterialFlags::NONE, CellTypeProperties {
+        density: 0.2,
+        colors: colors[&CellType::Smoke].clone(),
+        rand_color_pattern: RandColorPattern::None,
+        color_rand_radius: 0.25,
+        color_change_prob: 0.02,
+        movement_prob: 0.3,
+        fallthroug_prob: 1.0,
+        viscosity: 0.0,
+        dispersion: 1,
+        max_fall_speed: 1.0,
+        ignite_prob: 0.0,
+        ignition_temperature: None,
+        conductivity: 0.02,
+        heat_capacity: 0.1,
+        temperature: 50.0,
+        strength: 0.0,
+        burn_pressure: 0.0,
+        timer: 31,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.0,
+    }));
+    register(CellType::FlammableGass, Material::new("FlammableGass", CellPhase::Gass, MaterialFlags::NONE, CellTypeProperties {
+        density: 0.3,
+        colors: colors[&CellType::FlammableGass].clone(),
+        rand_color_pattern: RandColorPattern::None,
+        color_rand_radius: 0.25,
+        color_change_prob: 0.03,
+        movement_prob: 0.15,
+        fallthroug_prob: 1.0,
+        viscosity: 0.0,
+        dispersion: 2,
+        max_fall_speed: 1.0,
+        ignite_prob: 0.3,
+        ignition_temperature: Some(200.0),
+        conductivity: 0.2,
+        heat_capacity: 0.1,
+        temperature: 20.0,
+        strength: 0.0,
+        burn_pressure: 2.0,
+        timer: 4,
+        smoke_after_burnout: false,
+        fire_color_prob: 0.9,
+    }).with_label("F. Gass").with_hotkey(4));
+    register(CellType::Fire, Material::new("Fire", CellPhase::Gass, MaterialFlags::NONE, CellTypeProperties {
+        density: 0.1,
+        colors: colors[&CellType::Fire].clone(),
+        rand_color_pattern: RandColorPattern::None,
+        color_rand_radius: 0.25,
+        color_change_prob: 0.1,
+        movement_prob: 0.3,
+        fallthroug_prob: 1.0,
+        viscosity: 0.0,
+        dispersion: 1,
+        max_fall_speed: 1.0,
+        ignite_prob: 0.0,
+        ignition_temperature: None,
+        conductivity: 0.3,
+        heat_capacity: 1.0,
+        temperature: 1000.0,
+        strength: 0.0,
+        burn_pressure: 0.0,
+        timer: 2,
+        smoke_after_burnout: true,
+        fire_color_prob: 1.0,
+    }).with_hotkey(6));
+    register(CellType::Steam, Material::new("Steam", CellPhase::Gass, MaterialFlags::NONE, CellTypeProperties {
+        density: 0.15,
+        colors: colors[&CellType::Steam].clone(),
+        rand_color_pattern: RandColorPattern::None,
+        color_rand_radius: 0.25,
+        color_change_prob: 0.03,
+        movement_prob: 0.25,
+        fallthroug_prob: 1.0,
+        viscosity: 0.0,
+        dispersion: 2,
+        max_fall_speed: 1.0,
+        ignite_prob: 0.0,
+        ignition_temperature: None,
+        conductivity: 0.1,
+        heat_capacity: 0.5,
+        temperature: 110.0,
+        strength: 0.0,
+        burn_pressure: 0.0,
+        timer: 0,
+        smoke_after_burnout: true,
+        fire_color_prob: 1.0,
+    }).with_cooling(95.0, "Water"));
+    register(CellType::Water, Material::new("Water", CellPhase::Liquid, MaterialFlags::DISSOLVABLE, CellTypeProperties {
+        density: 2.0,
+        colors: colors[&CellType::Water].clone(),
+        rand_color_pattern: RandColorPattern::None,
+        color_rand_radius: 0.02,
+        color_change_prob: 0.01,
+        movement_prob: 0.9,
+        fallthroug_prob: 0.3,
+        viscosity: 0.0,
+        dispersion: 6,
+        max_fall_speed: 5.0,
+        ignite_prob: 0.0,
+        ignition_temperature: None,
+        conductivity: 0.3,
+        heat_capacity: 4.0,
+        temperature: 20.0,
+        strength: 0.0,
+        burn_pressure: 0.0,
+        timer: 0,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.0,
+    }).with_heating(100.0, "Steam").with_cooling(-2.0, "Ice").with_hotkey(2));
+    register(CellType::Oil, Material::new("Oil", CellPhase::Liquid, MaterialFlags::DISSOLVABLE, CellTypeProperties {
+        density: 1.5,
+        colors: colors[&CellType::Oil].clone(),
+        rand_color_pattern: RandColorPattern::None,
+        color_rand_radius: 0.02,
+        color_change_prob: 0.01,
+        movement_prob: 0.5,
+        fallthroug_prob: 0.3,
+        viscosity: 0.3,
+        dispersion: 2,
+        max_fall_speed: 4.0,
+        ignite_prob: 0.015,
+        ignition_temperature: Some(250.0),
+        conductivity: 0.1,
+        heat_capacity: 2.0,
+        temperature: 20.0,
+        strength: 0.0,
+        burn_pressure: 0.0,
+        timer: 15,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.6,
+    }).with_hotkey(5));
+    register(CellType::Acid, Material::new("Acid", CellPhase::Liquid, MaterialFlags::NONE, CellTypeProperties {
+        density: 1.0,
+        colors: colors[&CellType::Acid].clone(),
+        rand_color_pattern: RandColorPattern::None,
+        color_rand_radius: 0.1,
+        color_change_prob: 0.1,
+        movement_prob: 0.8,
+        fallthroug_prob: 0.3,
+        viscosity: 0.1,
+        dispersion: 4,
+        max_fall_speed: 4.0,
+        ignite_prob: 0.0,
+        ignition_temperature: None,
+        conductivity: 0.2,
+        heat_capacity: 3.0,
+        temperature: 20.0,
+        strength: 0.0,
+        burn_pressure: 0.0,
+        timer: 0,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.0,
+    }).with_hotkey(8));
+    register(CellType::Lava, Material::new("Lava", CellPhase::Liquid, MaterialFlags::DISSOLVABLE, CellTypeProperties {
+        density: 3.0,
+        colors: colors[&CellType::Lava].clone(),
+        rand_color_pattern: RandColorPattern::None,
+        color_rand_radius: 0.25,
+        color_change_prob: 0.02,
+        movement_prob: 0.4,
+        fallthroug_prob: 0.3,
+        viscosity: 0.7,
+        dispersion: 1,
+        max_fall_speed: 2.0,
+        ignite_prob: 0.0,
+        ignition_temperature: None,
+        conductivity: 0.1,
+        heat_capacity: 2.0,
+        temperature: 1200.0,
+        strength: 0.0,
+        burn_pressure: 0.0,
+        timer: 0,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.0,
+    }).with_cooling(700.0, "Stone"));
+    register(CellType::Stone, Material::new("Stone", CellPhase::StaticSolid, MaterialFlags::DISSOLVABLE, CellTypeProperties {
+        density: 10.0,
+        colors: colors[&CellType::Stone].clone(),
+        rand_color_pattern: RandColorPattern::Stretched { amount: 2, use_x: true, orig_prob: 0.1 },
+        color_rand_radius: 0.25,
+        color_change_prob: 0.0,
+        movement_prob: 1.0,
+        fallthroug_prob: 0.0,
+        viscosity: 0.0,
+        dispersion: 1,
+        max_fall_speed: 1.0,
+        ignite_prob: 0.0,
+        ignition_temperature: None,
+        conductivity: 0.1,
+        heat_capacity: 1.0,
+        temperature: 20.0,
+        strength: 60.0,
+        burn_pressure: 0.0,
+        timer: 0,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.0,
+    }).with_heating(1100.0, "Lava").with_cracked("Gravel").with_hotkey(3));
+    register(CellType::Wood, Material::new("Wood", CellPhase::StaticSolid, MaterialFlags::DISSOLVABLE, CellTypeProperties {
+        density: 10.0,
+        colors: colors[&CellType::Wood].clone(),
+        rand_color_pattern: RandColorPattern::Stretched { amount: 2, use_x: false, orig_prob: 0.05 },
+        color_rand_radius: 0.25,
+        color_change_prob: 0.0,
+        movement_prob: 1.0,
+        fallthroug_prob: 0.0,
+        viscosity: 0.0,
+        dispersion: 1,
+        max_fall_speed: 1.0,
+        ignite_prob: 0.01,
+        ignition_temperature: Some(300.0),
+        conductivity: 0.05,
+        heat_capacity: 1.5,
+        temperature: 20.0,
+        strength: 25.0,
+        burn_pressure: 0.0,
+        timer: 10,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.5,
+    }).with_hotkey(7));
+    register(CellType::Glass, Material::new("Glass", CellPhase::StaticSolid, MaterialFlags::NONE, CellTypeProperties {
+        density: 10.0,
+        colors: colors[&CellType::Glass].clone(),
+        rand_color_pattern: RandColorPattern::Stretched { amount: 5, use_x: false, orig_prob: 0.01 },
+        color_rand_radius: 0.15,
+        color_change_prob: 0.0,
+        movement_prob: 1.0,
+        fallthroug_prob: 0.0,
+        viscosity: 0.0,
+        dispersion: 1,
+        max_fall_speed: 1.0,
+        ignite_prob: 0.0,
+        ignition_temperature: None,
+        conductivity: 0.05,
+        heat_capacity: 1.0,
+        temperature: 20.0,
+        strength: 10.0,
+        burn_pressure: 0.0,
+        timer: 0,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.0,
+    }).with_cracked("Sand").with_hotkey(9));
+    register(CellType::Ice, Material::new("Ice", CellPhase::StaticSolid, MaterialFlags::DISSOLVABLE, CellTypeProperties {
+        density: 10.0,
+        colors: colors[&CellType::Ice].clone(),
+        rand_color_pattern: RandColorPattern::Stretched { amount: 3, use_x: true, orig_prob: 0.1 },
+        color_rand_radius: 0.15,
+        color_change_prob: 0.0,
+        movement_prob: 1.0,
+        fallthroug_prob: 0.0,
+        viscosity: 0.0,
+        dispersion: 1,
+        max_fall_speed: 1.0,
+        ignite_prob: 0.0,
+        ignition_temperature: None,
+        conductivity: 0.2,
+        heat_capacity: 2.0,
+        temperature: -60.0,
+        strength: 15.0,
+        burn_pressure: 0.0,
+        timer: 0,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.0,
+    }).with_heating(0.0, "Water"));
+    register(CellType::Sand, Material::new("Sand", CellPhase::Powder, MaterialFlags::DISSOLVABLE, CellTypeProperties {
+        density: 10.0,
+        colors: colors[&CellType::Sand].clone(),
+        rand_color_pattern: RandColorPattern::None,
+        color_rand_radius: 0.25,
+        color_change_prob: 0.0,
+        movement_prob: 0.95,
+        fallthroug_prob: 0.0,
+        viscosity: 0.0,
+        dispersion: 1,
+        max_fall_speed: 4.0,
+        ignite_prob: 0.0,
+        ignition_temperature: None,
+        conductivity: 0.05,
+        heat_capacity: 1.0,
+        temperature: 20.0,
+        strength: 2.0,
+        burn_pressure: 0.0,
+        timer: 0,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.0,
+    }).with_heating(1100.0, "Glass").with_hotkey(1));
+    register(CellType::Coal, Material::new("Coal", CellPhase::Powder, MaterialFlags::DISSOLVABLE, CellTypeProperties {
+        density: 10.0,
+        colors: colors[&CellType::Coal].clone(),
+        rand_color_pattern: RandColorPattern::None,
+        color_rand_radius: 0.25,
+        color_change_prob: 0.0,
+        movement_prob: 0.1,
+        fallthroug_prob: 0.0,
+        viscosity: 0.0,
+        dispersion: 1,
+        max_fall_speed: 3.0,
+        ignite_prob: 0.01,
+        ignition_temperature: Some(400.0),
+        conductivity: 0.05,
+        heat_capacity: 1.0,
+        temperature: 20.0,
+        strength: 2.0,
+        burn_pressure: 0.0,
+        timer: 20,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.5,
+    }));
+
+    // not built-in, the rules don't refer to it
+    materials.register(Material::new("Gravel", CellPhase::Powder, MaterialFlags::DISSOLVABLE, CellTypeProperties {
+        density: 10.0,
+        colors: CellColors::CentricRGB { color: Srgba::hex("868188").unwrap().into() },
+        rand_color_pattern: RandColorPattern::None,
+        color_rand_radius: 0.25,
+        color_change_prob: 0.0,
+        movement_prob: 0.6,
+        fallthroug_prob: 0.0,
+        viscosity: 0.0,
+        dispersion: 1,
+        max_fall_speed: 5.0,
+        ignite_prob: 0.0,
+        ignition_temperature: None,
+        conductivity: 0.05,
+        heat_capacity: 1.0,
+        temperature: 20.0,
+        strength: 4.0,
+        burn_pressure: 0.0,
+        timer: 0,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.0,
+    }).with_heating(1100.0, "Lava"));
+    let honey = materials.register(Material::new("Honey", CellPhase::Liquid, MaterialFlags::DISSOLVABLE, CellTypeProperties {
+        density: 2.5,
+        colors: CellColors::CentricRGB { color: Srgba::hex("E0A526").unwrap().into() },
+        rand_color_pattern: RandColorPattern::None,
+        color_rand_radius: 0.03,
+        color_change_prob: 0.005,
+        movement_prob: 0.5,
+        fallthroug_prob: 0.1,
+        viscosity: 0.9,
+        dispersion: 1,
+        max_fall_speed: 1.5,
+        ignite_prob: 0.0,
+        ignition_temperature: None,
+        conductivity: 0.2,
+        heat_capacity: 3.0,
+        temperature: 20.0,
+        strength: 0.0,
+        burn_pressure: 0.0,
+        timer: 0,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.0,
+    }));
+    let metal = materials.register(Material::new("Metal", CellPhase::StaticSolid, MaterialFlags::DISSOLVABLE.union(MaterialFlags::CONDUCTIVE), CellTypeProperties {
+        density: 10.0,
+        colors: CellColors::CentricRGB { color: Srgba::hex("8B9BB4").unwrap().into() },
+        rand_color_pattern: RandColorPattern::Stretched { amount: 4, use_x: true, orig_prob: 0.02 },
+        color_rand_radius: 0.1,
+        color_change_prob: 0.0,
+        movement_prob: 1.0,
+        fallthroug_prob: 0.0,
+        viscosity: 0.0,
+        dispersion: 1,
+        max_fall_speed: 1.0,
+        ignite_prob: 0.0,
+        ignition_temperature: None,
+        conductivity: 0.5,
+        heat_capacity: 1.0,
+        temperature: 20.0,
+        strength: 80.0,
+        burn_pressure: 0.0,
+        timer: 0,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.0,
+    }));
+    let battery = materials.register(Material::new("Battery", CellPhase::StaticSolid, MaterialFlags::POWER_SOURCE, CellTypeProperties {
+        density: 10.0,
+        colors: CellColors::CentricRGB { color: Srgba::hex("3E8948").unwrap().into() },
+        rand_color_pattern: RandColorPattern::None,
+        color_rand_radius: 0.05,
+        color_change_prob: 0.0,
+        movement_prob: 1.0,
+        fallthroug_prob: 0.0,
+        viscosity: 0.0,
+        dispersion: 1,
+        max_fall_speed: 1.0,
+        ignite_prob: 0.0,
+        ignition_temperature: None,
+        conductivity: 0.05,
+        heat_capacity: 1.0,
+        temperature: 20.0,
+        strength: 40.0,
+        burn_pressure: 0.0,
+        timer: 0,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.0,
+    }));
+    // the circuit parts only differ in their color
+    let logic_part = |hex: &str| CellTypeProperties {
+        density: 10.0,
+        colors: CellColors::CentricRGB { color: Srgba::hex(hex).unwrap().into() },
+        rand_color_pattern: RandColorPattern::None,
+        color_rand_radius: 0.05,
+        color_change_prob: 0.0,
+        movement_prob: 1.0,
+        fallthroug_prob: 0.0,
+        viscosity: 0.0,
+        dispersion: 1,
+        max_fall_speed: 1.0,
+        ignite_prob: 0.0,
+        ignition_temperature: None,
+        conductivity: 0.05,
+        heat_capacity: 1.0,
+        temperature: 20.0,
+        strength: 40.0,
+        burn_pressure: 0.0,
+        timer: 0,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.0,
+    };
+    let sensor = materials.register(Material::new("Sensor", CellPhase::StaticSolid, MaterialFlags::NONE, logic_part("C28569"))
+        .with_logic(Logic::Sensor(Reactant::Phase(CellPhase::Powder))));
+    let and = materials.register(Material::new("And", CellPhase::StaticSolid, MaterialFlags::NONE, logic_part("5A6988"))
+        .with_logic(Logic::And));
+    let or = materials.register(Material::new("Or", CellPhase::StaticSolid, MaterialFlags::NONE, logic_part("3A4466"))
+        .with_logic(Logic::Or));
+    let not = materials.register(Material::new("Not", CellPhase::StaticSolid, MaterialFlags::NONE, logic_part("68386C"))
+        .with_logic(Logic::Not));
+    let delay = materials.register(Material::new("Delay", CellPhase::StaticSolid, MaterialFlags::NONE, logic_part("B86F50"))
+        .with_logic(Logic::Delay(8)));
+    let door = materials.register(Material::new("Door", CellPhase::StaticSolid, MaterialFlags::NONE, logic_part("733E39"))
+        .with_logic(Logic::Door));
+    let emitter = materials.register(Material::new("Emitter", CellPhase::StaticSolid, MaterialFlags::NONE, logic_part("2CE8F5"))
+        .with_logic(Logic::Emitter { material: String::from("Water"), prob: 0.1 }));
+    let seed = materials.register(Material::new("Seed", CellPhase::Powder, MaterialFlags::DISSOLVABLE, CellTypeProperties {
+        density: 10.0,
+        colors: CellColors::CentricRGB { color: Srgba::hex("C8A26B").unwrap().into() },
+        rand_color_pattern: RandColorPattern::None,
+        color_rand_radius: 0.2,
+        color_change_prob: 0.0,
+        movement_prob: 0.1,
+        fallthroug_prob: 0.0,
+        viscosity: 0.0,
+        dispersion: 1,
+        max_fall_speed: 3.0,
+        ignite_prob: 0.05,
+        ignition_temperature: Some(250.0),
+        conductivity: 0.05,
+        heat_capacity: 1.0,
+        temperature: 20.0,
+        strength: 2.0,
+        burn_pressure: 0.0,
+        timer: 4,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.5,
+    }).with_plant(Plant::Seed { soil: String::from("Sand"), stem: String::from("Stem"), root: String::from("Root") }));
+    // the plant parts burn like wood, only they catch fire sooner and burn out faster
+    let plant_part = |hex: &str| CellTypeProperties {
+        density: 10.0,
+        colors: CellColors::CentricRGB { color: Srgba::hex(hex).unwrap().into() },
+        rand_color_pattern: RandColorPattern::Stretched { amount: 2, use_x: false, orig_prob: 0.05 },
+        color_rand_radius: 0.2,
+        color_change_prob: 0.0,
+        movement_prob: 1.0,
+        fallthroug_prob: 0.0,
+        viscosity: 0.0,
+        dispersion: 1,
+        max_fall_speed: 1.0,
+        ignite_prob: 0.05,
+        ignition_temperature: Some(250.0),
+        conductivity: 0.05,
+        heat_capacity: 1.5,
+        temperature: 20.0,
+        strength: 20.0,
+        burn_pressure: 0.0,
+        timer: 4,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.5,
+    };
+    materials.register(Material::new("Stem", CellPhase::StaticSolid, MaterialFlags::DISSOLVABLE, plant_part("63C74D"))
+        .with_plant(Plant::Stem { dir: IVec2::Y, branch_prob: 0.15, stop_prob: 0.05 }));
+    materials.register(Material::new("Root", CellPhase::StaticSolid, MaterialFlags::DISSOLVABLE, plant_part("8F563B"))
+        .with_plant(Plant::Root { soil: String::from("Sand"), stop_prob: 0.15 }));
+    materials.register(Material::new("Remains", CellPhase::Powder, MaterialFlags::DISSOLVABLE, CellTypeProperties {
+        density: 10.0,
+        colors: CellColors::CentricRGB { color: Srgba::hex("5D275D").unwrap().into() },
+        rand_color_pattern: RandColorPattern::None,
+        color_rand_radius: 0.2,
+        color_change_prob: 0.0,
+        movement_prob: 0.1,
+        fallthroug_prob: 0.0,
+        viscosity: 0.0,
+        dispersion: 1,
+        max_fall_speed: 3.0,
+        ignite_prob: 0.02,
+        ignition_temperature: Some(250.0),
+        conductivity: 0.05,
+        heat_capacity: 1.0,
+        temperature: 20.0,
+        strength: 2.0,
+        burn_pressure: 0.0,
+        timer: 6,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.5,
+    }));
+    let ant = materials.register(Material::new("Ant", CellPhase::Powder, MaterialFlags::DISSOLVABLE, CellTypeProperties {
+        density: 10.0,
+        colors: CellColors::CentricRGB { color: Srgba::hex("181425").unwrap().into() },
+        rand_color_pattern: RandColorPattern::None,
+        color_rand_radius: 0.1,
+        color_change_prob: 0.0,
+        movement_prob: 0.1,
+        fallthroug_prob: 0.0,
+        viscosity: 0.0,
+        dispersion: 1,
+        max_fall_speed: 3.0,
+        ignite_prob: 0.1,
+        ignition_temperature: Some(150.0),
+        conductivity: 0.05,
+        heat_capacity: 1.0,
+        temperature: 20.0,
+        strength: 2.0,
+        burn_pressure: 0.0,
+        timer: 3,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.5,
+    }).with_creature(Creature {
+        behavior: Behavior::Walk { dig: String::from("Sand"), dig_prob: 0.1 },
+        move_prob: 0.3,
+        lifespan: 60,
+        remains: String::from("Remains"),
+    }));
+    // as heavy as water, so it neither sinks nor floats and only moves by swimming
+    // the liquid pressure leaves it alone, but its weight loads the structures like any other liquid
+    let fish = materials.register(Material::new("Fish", CellPhase::Liquid, MaterialFlags::DISSOLVABLE, CellTypeProperties {
+        density: 2.0,
+        colors: CellColors::CentricRGB { color: Srgba::hex("FEAE34").unwrap().into() },
+        rand_color_pattern: RandColorPattern::None,
+        color_rand_radius: 0.1,
+        color_change_prob: 0.0,
+        movement_prob: 0.5,
+        fallthroug_prob: 0.0,
+        viscosity: 0.0,
+        dispersion: 1,
+        max_fall_speed: 5.0,
+        ignite_prob: 0.05,
+        ignition_temperature: Some(200.0),
+        conductivity: 0.3,
+        heat_capacity: 4.0,
+        temperature: 20.0,
+        strength: 0.0,
+        burn_pressure: 0.0,
+        timer: 3,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.5,
+    }).with_creature(Creature {
+        behavior: Behavior::Swim { medium: String::from("Water"), suffocate_prob: 0.01 },
+        move_prob: 0.2,
+        lifespan: 100,
+        remains: String::from("Remains"),
+    }));
+    let gunpowder = materials.register(Material::new("Gunpowder", CellPhase::Powder, MaterialFlags::DISSOLVABLE, CellTypeProperties {
+        density: 10.0,
+        colors: CellColors::CentricRGB { color: Srgba::hex("262B44").unwrap().into() },
+        rand_color_pattern: RandColorPattern::None,
+        color_rand_radius: 0.3,
+        color_change_prob: 0.0,
+        movement_prob: 0.2,
+        fallthroug_prob: 0.0,
+        viscosity: 0.0,
+        dispersion: 1,
+        max_fall_speed: 3.0,
+        ignite_prob: 0.9,
+        ignition_temperature: Some(200.0),
+        conductivity: 0.05,
+        heat_capacity: 1.0,
+        temperature: 20.0,
+        strength: 2.0,
+        burn_pressure: 0.0,
+        timer: 2,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.8,
+    }).with_explosive(Detonation::Burnout, 3.0, 60.0));
+    let tnt = materials.register(Material::new("TNT", CellPhase::StaticSolid, MaterialFlags::DISSOLVABLE, CellTypeProperties {
+        density: 10.0,
+        colors: CellColors::CentricRGB { color: Srgba::hex("E43B44").unwrap().into() },
+        rand_color_pattern: RandColorPattern::Stretched { amount: 3, use_x: false, orig_prob: 0.05 },
+        color_rand_radius: 0.1,
+        color_change_prob: 0.0,
+        movement_prob: 1.0,
+        fallthroug_prob: 0.0,
+        viscosity: 0.0,
+        dispersion: 1,
+        max_fall_speed: 1.0,
+        ignite_prob: 0.5,
+        ignition_temperature: Some(250.0),
+        conductivity: 0.05,
+        heat_capacity: 1.0,
+        temperature: 20.0,
+        strength: 20.0,
+        burn_pressure: 0.0,
+        timer: 8,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.3,
+    }).with_explosive(Detonation::Burnout, 10.0, 300.0));
+    let nitro = materials.register(Material::new("Nitro", CellPhase::Liquid, MaterialFlags::DISSOLVABLE, CellTypeProperties {
+        density: 1.8,
+        colors: CellColors::CentricRGB { color: Srgba::hex("B8E06C").unwrap().into() },
+        rand_color_pattern: RandColorPattern::None,
+        color_rand_radius: 0.05,
+        color_change_prob: 0.01,
+        movement_prob: 0.8,
+        fallthroug_prob: 0.3,
+        viscosity: 0.0,
+        dispersion: 4,
+        max_fall_speed: 5.0,
+        ignite_prob: 0.8,
+        ignition_temperature: Some(150.0),
+        conductivity: 0.2,
+        heat_capacity: 2.0,
+        temperature: 20.0,
+        strength: 0.0,
+        burn_pressure: 0.0,
+        timer: 1,
+        smoke_after_burnout: true,
+        fire_color_prob: 0.5,
+    }).with_explosive(Detonation::Impact(1.5), 7.0, 200.0));
+    materials.buttons = vec![
+        CellType::Sand,
+        CellType::Water,
+        CellType::Wood,
+        CellType::Stone,
+        CellType::Coal,
+        CellType::Fire,
+        CellType::FlammableGass,
+        CellType::Oil,
+        CellType::Glass,
+        CellType::Acid,
+        CellType::Lava,
+        CellType::Ice,
+        honey,
+        metal,
+        battery,
+        sensor,
+        and,
+        or,
+        not,
+        delay,
+        door,
+        emitter,
+        seed,
+        ant,
+        fish,
+        gunpowder,
+        tnt,
+        nitro,
+    ];
+    materials
+}
+
+pub fn get_default_reactions() -> Vec<Reaction>
+{
+    let material = Reactant::material;
+    vec![
+        // acid neutralized by water below it
+        Reaction::contact(material("Acid"), material("Water"), Neighborhood::Down, 0.015)
+            .with_cell_product("Water"),
+        // acid dissolving materials
+        Reaction::contact(material("Acid"), material("Water"), Neighborhood::Below, 0.05)
+            .with_cell_product("Smoke"),
+        Reaction::contact(material("Acid"), material("Ice"), Neighborhood::Below, 0.05)
+            .with_cell_product("Smoke")
+            .with_neighbor_product("Water"),
+        Reaction::contact(material("Acid"), material("Lava"), Neighborhood::Below, 0.05)
+            .with_cell_product("FlammableGass")
+            .with_neighbor_product("Fire"),
+        Reaction::contact(material("Acid"), Reactant::Flags(MaterialFlags::DISSOLVABLE), Neighborhood::Below, 0.05)
+            .with_cell_product("FlammableGass")
+            .with_neighbor_product("Air"),
+        // creatures drowning under water and dying in acid
+        Reaction::contact(material("Ant"), material("Water"), Neighborhood::Up, 0.01)
+            .with_cell_product("Remains"),
+        Reaction::contact(material("Fish"), material("Acid"), Neighborhood::Moore, 0.1)
+            .with_cell_product("Remains"),
+        // water extinguishing flame
+        Reaction::contact(material("Water"), material("Fire"), Neighborhood::Below, 1.0)
+            .with_neighbor_product("Smoke"),
+        // flames around burning materials, the flames themselves don't spread into the air
+        Reaction::ignition(material("Fire"), material("Air")),
+        Reaction::ignition(Reactant::Burning, material("Air"))
+            .with_neighbor_product("Fire"),
+    ]
+}
@@ -1,34 +1,76 @@
 use std::collections::HashSet;
 
 use bevy::prelude::*;
-use enum_map::EnumMap;
 
 use rand::prelude::*;
-use crate::ui::BrushType;
+use crate::cell_grid::BrushType;
+use crate::img_utils::*;
 use crate::utils::*;
 use crate::cell::*;
+use crate::materials::*;
+use crate::particles::*;
+use crate::electricity::FULL_CHARGE;
+
+// window of the grid shown in the render image, the image rows go from the top of the window down
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GridView
+{
+    // bottom left cell
+    pub pos: IVec2,
+    pub sizes: IVec2,
+}
+
+impl GridView {
+    pub fn to_image(&self, pos: IVec2) -> IVec2 {
+        IVec2::new(pos.x - self.pos.x, self.pos.y + self.sizes.y - pos.y - 1)
+    }
+
+    pub fn to_grid(&self, pixel: IVec2) -> IVec2 {
+        IVec2::new(self.pos.x + pixel.x, self.pos.y + self.sizes.y - pixel.y - 1)
+    }
+
+    pub fn contains_pixel(&self, pixel: IVec2) -> bool {
+        pixel.x >= 0 && pixel.y >= 0 && pixel.x < self.sizes.x && pixel.y < self.sizes.y
+    }
+
+    pub fn pixel_count(&self) -> usize {
+        (self.sizes.x * self.sizes.y) as usize
+    }
+
+    pub fn pixel_index(&self, pixel: IVec2) -> usize {
+        (pixel.x + self.sizes.x * pixel.y) as usize
+    }
+
+    pub fn index_pixel(&self, i: usize) -> IVec2 {
+        IVec2::new(i as i32 % self.sizes.x, i as i32 / self.sizes.x)
+    }
+}
 
 pub struct GridDisplay
 {
     pub shallow_water_color: Color,
     pub brush_edge_color: Color,
+    // glow of the charged conductors
+    pub charge_color: Color,
 }
 
 impl GridDisplay {
 
-    pub fn display(&self, cells: &Vector2D<Cell>, cell_properties: &EnumMap<CellType, CellTypeProperties>, out_image: &mut Image)
+    pub fn display(&self, cells: &Vector2D<Cell>, materials: &MaterialRegistry, view: &GridView, out_image: &mut Image)
     {
-        for i in 0..cells.data.len() {
-            let iv = cells.index_to_vec(i);
-            let iv = IVec2 { x: iv.x, y: cells.sizes.y - iv.y - 1 };
-            let rel_pos = iv.as_vec2() / (cells.sizes - 1).as_vec2();
-            let background_color = cell_properties[CellType::Air].get_color_rgba(1.0, 0, rel_pos).xyz();
+        for i in 0..view.pixel_count() {
+            let iv = view.to_grid(view.index_pixel(i));
+            let rel_pos = IVec2::new(iv.x, cells.sizes.y - iv.y - 1).as_vec2() / (cells.sizes - 1).as_vec2();
+            let background_color = materials[CellType::Air].get_color_rgba(1.0, 0, rel_pos).xyz();
             let cell_type = cells[iv].cell_type;
             let color_scale = cells[iv].color_scale();
             let duration = cells[iv].get_timer();
-            let mut color = cell_properties[cell_type].get_color_rgba(color_scale, duration, rel_pos);
+            let mut color = materials[cell_type].get_color_rgba(color_scale, duration, rel_pos);
             if cells[iv].is_on_fire() && cells[iv].uses_fire_color() {
-                color = cell_properties[CellType::Fire].get_color_rgba(color_scale, duration, rel_pos);
+                color = materials[CellType::Fire].get_color_rgba(color_scale, duration, rel_pos);
+            }
+            if cells[iv].charge > 0 {
+                color = color.lerp(self.charge_color.to_linear().to_vec4(), cells[iv].charge as f32 / FULL_CHARGE as f32);
             }
             let rgb = color.xyz();
             let a = color.w;
@@ -39,35 +81,94 @@ impl GridDisplay {
         }
     }
     
-    pub fn draw_brush_edge(&self, cells: &Vector2D<Cell>, out_image: &mut Image, pos: IVec2, prev_pos: Option<IVec2>, brush: BrushType, size: i32)
+    // colors the cells by their temperature instead of their material
+    pub fn display_heat_map(&self, cells: &Vector2D<Cell>, view: &GridView, out_image: &mut Image)
+    {
+        for i in 0..view.pixel_count() {
+            let iv = view.to_grid(view.index_pixel(i));
+            let color = heat_map_color(cells[iv].temperature).clamp(Vec3::splat(0.0), Vec3::splat(1.0)) * 255.0;
+            out_image.data[i*4 + 0] = color[0] as u8;
+            out_image.data[i*4 + 1] = color[1] as u8;
+            out_image.data[i*4 + 2] = color[2] as u8;
+        }
+    }
+
+    // particles are drawn over the cells of the grid
+    pub fn display_particles(&self, particles: &[Particle], cells: &Vector2D<Cell>, materials: &MaterialRegistry, view: &GridView, out_image: &mut Image)
+    {
+        for particle in particles {
+            let pos = particle.cell_pos();
+            let iv = view.to_image(pos);
+            if !cells.is_in_range(pos) || !view.contains_pixel(iv) {
+                continue;
+            }
+            let rel_pos = IVec2::new(pos.x, cells.sizes.y - pos.y - 1).as_vec2() / (cells.sizes - 1).as_vec2();
+            let cell = particle.cell;
+            let mut color = materials[cell.cell_type].get_color_rgba(cell.color_scale(), cell.get_timer(), rel_pos);
+            if cell.is_on_fire() && cell.uses_fire_color() {
+                color = materials[CellType::Fire].get_color_rgba(cell.color_scale(), cell.get_timer(), rel_pos);
+            }
+            let i = view.pixel_index(iv);
+            let background_color = Vec3::new(out_image.data[i*4 + 0] as f32, out_image.data[i*4 + 1] as f32, out_image.data[i*4 + 2] as f32) / 255.0;
+            let color = (color.w * color.xyz() + (1.0 - color.w) * background_color).clamp(Vec3::splat(0.0), Vec3::splat(1.0)) * 255.0;
+            out_image.data[i*4 + 0] = color[0] as u8;
+            out_image.data[i*4 + 1] = color[1] as u8;
+            out_image.data[i*4 + 2] = color[2] as u8;
+        }
+    }
+
+    pub fn display_particles_heat_map(&self, particles: &[Particle], cells: &Vector2D<Cell>, view: &GridView, out_image: &mut Image)
+    {
+        for particle in particles {
+            let pos = particle.cell_pos();
+            let iv = view.to_image(pos);
+            if !cells.is_in_range(pos) || !view.contains_pixel(iv) {
+                continue;
+            }
+            let i = view.pixel_index(iv);
+            let color = heat_map_color(particle.cell.temperature).clamp(Vec3::splat(0.0), Vec3::splat(1.0)) * 255.0;
+            out_image.data[i*4 + 0] = color[0] as u8;
+            out_image.data[i*4 + 1] = color[1] as u8;
+            out_image.data[i*4 + 2] = color[2] as u8;
+        }
+    }
+
+    pub fn draw_brush_edge(&self, view: &GridView, out_image: &mut Image, pos: IVec2, prev_pos: Option<IVec2>, brush: BrushType, size: i32)
     {
         match brush {
             BrushType::Circle => {
-                self.draw_brush_edge_circle(cells, out_image, pos, size);
+                self.draw_brush_edge_circle(view, out_image, pos, size);
             },
             BrushType::Square => {
-                self.draw_brush_edge_square(cells, out_image, pos, size);
+                self.draw_brush_edge_square(view, out_image, pos, size);
             },
             BrushType::LineRound => {
                 if let Some(prev_pos) = prev_pos {
-                    self.draw_brush_edge_line_round(cells, out_image, prev_pos, pos, size);
+                    self.draw_brush_edge_line_round(view, out_image, prev_pos, pos, size);
                 } else {
-                    self.draw_brush_edge_circle(cells, out_image, pos, size);
+                    self.draw_brush_edge_circle(view, out_image, pos, size);
                 }
             },
             BrushType::LineSharp => {
                 if let Some(prev_pos) = prev_pos {
-                    self.draw_brush_edge_line_sharp(cells, out_image, prev_pos, pos, size);
+                    self.draw_brush_edge_line_sharp(view, out_image, prev_pos, pos, size);
+                } else {
+                    self.draw_brush_edge_square(view, out_image, pos, size);
+                }
+            },
+            BrushType::Fling => {
+                if let Some(prev_pos) = prev_pos {
+                    self.draw_brush_edge_fling(view, out_image, prev_pos, pos, size);
                 } else {
-                    self.draw_brush_edge_square(cells, out_image, pos, size);
+                    self.draw_brush_edge_circle(view, out_image, pos, size);
                 }
             },
         }
     }
 
-    pub fn set_brush_color(&self, cells: &Vector2D<Cell>, iv: IVec2, out_image: &mut Image, color: Vec3, a: f32)
+    pub fn set_brush_color(&self, view: &GridView, iv: IVec2, out_image: &mut Image, color: Vec3, a: f32)
     {
-        let i = 4 * cells.vec_to_index(iv);
+        let i = 4 * view.pixel_index(iv);
         let in_color: Color = LinearRgba::from_u8_array_no_alpha([
             out_image.data[i + 0],
             out_image.data[i + 1],
@@ -78,16 +179,16 @@ impl GridDisplay {
         set_img_color(iv.as_uvec2(), col.mix(&in_color, 0.8), out_image);
     }
 
-    pub fn draw_brush_edge_circle(&self, cells: &Vector2D<Cell>, out_image: &mut Image, pos: IVec2, size: i32)
+    pub fn draw_brush_edge_circle(&self, view: &GridView, out_image: &mut Image, pos: IVec2, size: i32)
     {
-        let pos = IVec2 { x: pos.x, y: cells.sizes.y - pos.y - 1 };
+        let pos = view.to_image(pos);
         let lin_color = self.brush_edge_color.to_linear();
         let color = lin_color.to_vec3();
         let a = lin_color.alpha;
 
         let mut positions = HashSet::<IVec2>::new();
         let mut set_color = |pos: IVec2| {
-            if cells.is_in_range(pos) {
+            if view.contains_pixel(pos) {
                 positions.insert(pos);
             }
         };
@@ -95,13 +196,13 @@ impl GridDisplay {
         bresenham_circle_edge(pos, size, &mut set_color);
 
         for pos in positions {
-            self.set_brush_color(cells, pos, out_image, color, a);
+            self.set_brush_color(view, pos, out_image, color, a);
         }
     }
 
-    pub fn draw_brush_edge_square(&self, cells: &Vector2D<Cell>, out_image: &mut Image, pos: IVec2, size: i32)
+    pub fn draw_brush_edge_square(&self, view: &GridView, out_image: &mut Image, pos: IVec2, size: i32)
     {
-        let pos = IVec2 { x: pos.x, y: cells.sizes.y - pos.y - 1 };
+        let pos = view.to_image(pos);
         let lin_color = self.brush_edge_color.to_linear();
         let color = lin_color.to_vec3();
         let a = lin_color.alpha;
@@ -110,24 +211,24 @@ impl GridDisplay {
         for y in start_pos.y..end_pos.y {
             for x in start_pos.x..end_pos.x {
                 let iv = IVec2::new(x, y);
-                if cells.is_in_range(iv) && (x == start_pos.x || y == start_pos.y || x == end_pos.x - 1 || y == end_pos.y - 1) {
-                    self.set_brush_color(cells, iv, out_image, color, a);
+                if view.contains_pixel(iv) && (x == start_pos.x || y == start_pos.y || x == end_pos.x - 1 || y == end_pos.y - 1) {
+                    self.set_brush_color(view, iv, out_image, color, a);
                 }
             }
         }
     }
 
-    pub fn draw_brush_edge_line_round(&self, cells: &Vector2D<Cell>, out_image: &mut Image, pos_from: IVec2, pos_to: IVec2, size: i32)
+    pub fn draw_brush_edge_line_round(&self, view: &GridView, out_image: &mut Image, pos_from: IVec2, pos_to: IVec2, size: i32)
     {
-        let pos_from = IVec2 { x: pos_from.x, y: cells.sizes.y - pos_from.y - 1 };
-        let pos_to = IVec2 { x: pos_to.x, y: cells.sizes.y - pos_to.y - 1 };
+        let pos_from = view.to_image(pos_from);
+        let pos_to = view.to_image(pos_to);
         let lin_color = self.brush_edge_color.to_linear();
         let color = lin_color.to_vec3();
         let a = lin_color.alpha;
         
         let mut positions = HashSet::<IVec2>::new();
         let mut set_color = |pos: IVec2| {
-            if cells.is_in_range(pos) {
+            if view.contains_pixel(pos) {
                 positions.insert(pos);
             }
         };
@@ -137,21 +238,21 @@ impl GridDisplay {
         bresenham_circle_edge(pos_to, size, &mut set_color);
         
         for pos in positions {
-            self.set_brush_color(cells, pos, out_image, color, a);
+            self.set_brush_color(view, pos, out_image, color, a);
         }
     }
 
-    pub fn draw_brush_edge_line_sharp(&self, cells: &Vector2D<Cell>, out_image: &mut Image, pos_from: IVec2, pos_to: IVec2, size: i32)
+    pub fn draw_brush_edge_line_sharp(&self, view: &GridView, out_image: &mut Image, pos_from: IVec2, pos_to: IVec2, size: i32)
     {
-        let pos_from = IVec2 { x: pos_from.x, y: cells.sizes.y - pos_from.y - 1 };
-        let pos_to = IVec2 { x: pos_to.x, y: cells.sizes.y - pos_to.y - 1 };
+        let pos_from = view.to_image(pos_from);
+        let pos_to = view.to_image(pos_to);
         let lin_color = self.brush_edge_color.to_linear();
         let color = lin_color.to_vec3();
         let a = lin_color.alpha;
 
         let mut positions = HashSet::<IVec2>::new();
         let mut set_color = |pos: IVec2| {
-            if cells.is_in_range(pos) {
+            if view.contains_pixel(pos) {
                 positions.insert(pos);
             }
         };
@@ -159,8 +260,59 @@ impl GridDisplay {
         dda_thick_outline(pos_from, pos_to, size, &mut set_color);
 
         for pos in positions {
-            self.set_brush_color(cells, pos, out_image, color, a);
+            self.set_brush_color(view, pos, out_image, color, a);
         }
     }
 
-}
\ No newline at end of file
+    // the flung circle with the direction of the throw
+    pub fn draw_brush_edge_fling(&self, view: &GridView, out_image: &mut Image, pos_from: IVec2, pos_to: IVec2, size: i32)
+    {
+        let pos_from = view.to_image(pos_from);
+        let pos_to = view.to_image(pos_to);
+        let lin_color = self.brush_edge_color.to_linear();
+        let color = lin_color.to_vec3();
+        let a = lin_color.alpha;
+
+        let mut positions = HashSet::<IVec2>::new();
+        let mut set_color = |pos: IVec2| {
+            if view.contains_pixel(pos) {
+                positions.insert(pos);
+            }
+        };
+        
+        bresenham_circle_edge(pos_from, size, &mut set_color);
+        dda(pos_from, pos_to, &mut set_color);
+
+        for pos in positions {
+            self.set_brush_color(view, pos, out_image, color, a);
+        }
+    }
+
+}
+
+// temperatures with their colors, the colors in between are interpolated
+const HEAT_MAP_COLORS: [(f32, Vec3); 7] = [
+    (-60.0, Vec3::new(0.6, 0.8, 1.0)),
+    (0.0, Vec3::new(0.1, 0.3, 0.8)),
+    (20.0, Vec3::new(0.05, 0.05, 0.1)),
+    (100.0, Vec3::new(0.5, 0.0, 0.3)),
+    (400.0, Vec3::new(0.9, 0.1, 0.0)),
+    (800.0, Vec3::new(1.0, 0.6, 0.0)),
+    (1200.0, Vec3::new(1.0, 1.0, 0.8)),
+];
+
+pub fn heat_map_color(temperature: f32) -> Vec3
+{
+    let (first_temperature, first_color) = HEAT_MAP_COLORS[0];
+    if temperature <= first_temperature {
+        return first_color;
+    }
+    for i in 1..HEAT_MAP_COLORS.len() {
+        let (from_temperature, from_color) = HEAT_MAP_COLORS[i - 1];
+        let (to_temperature, to_color) = HEAT_MAP_COLORS[i];
+        if temperature <= to_temperature {
+            return from_color.lerp(to_color, (temperature - from_temperature) / (to_temperature - from_temperature));
+        }
+    }
+    HEAT_MAP_COLORS[HEAT_MAP_COLORS.len() - 1].1
+}
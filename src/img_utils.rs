@@ -0,0 +1,30 @@
+use bevy::prelude::*;
+
+pub fn fill_img_color(color: Color, out_image: &mut Image)
+{
+    for y in 0..out_image.size().y {
+        for x in 0..out_image.size().x {
+            set_img_color(UVec2::new(x, y), color, out_image);
+        }
+    }
+}
+
+pub fn fill_sub_img_color(color: Color, out_image: &mut Image, from: UVec2, to: UVec2)
+{
+    let from = from.clamp(UVec2::ZERO, out_image.size());
+    let to = to.clamp(UVec2::ZERO, out_image.size());
+    for y in from.y..to.y {
+        for x in from.x..to.x {
+            set_img_color(UVec2::new(x, y), color, out_image);
+        }
+    }
+}
+
+pub fn set_img_color(pos: UVec2, color: Color, out_image: &mut Image)
+{
+    let i = (pos.x + pos.y * out_image.size().x) as usize;
+    let color = color.to_linear().to_u8_array();
+    for ch in 0..4 {
+        out_image.data[i*4 + ch] = color[ch];
+    }
+}
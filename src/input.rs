@@ -1,13 +1,18 @@
-use bevy::{input::{mouse::MouseWheel, touch::Touch}, math::*, prelude::*, ui::RelativeCursorPosition, window::{PrimaryWindow, Window}};
+use std::f32::consts::FRAC_PI_4;
 
-use crate::{cell::CellType, ui::{BrushSizeText, BrushType, DrawingCanvas}, utils::*, GameGlobals};
+use bevy::{input::{mouse::{MouseMotion, MouseWheel}, touch::Touch}, math::*, prelude::*, ui::RelativeCursorPosition, window::{PrimaryWindow, Window}};
+
+use crate::{cell::CellType, cell_grid::BrushType, ui::{BrushSizeText, DrawingCanvas}, utils::*, GameGlobals};
+
+// part of the view the arrows move it by in a frame
+const VIEW_PAN_SPEED: f32 = 0.02;
 
 pub fn get_out_img_cursor_pos(relative_cursor_position: &RelativeCursorPosition, globals: &GameGlobals) -> Option<IVec2>
 {
     if let Some(rel_cursor_position) = relative_cursor_position.normalized {
-        let cell_pos = (rel_cursor_position * (globals.img_size as f32)).as_ivec2();
-        let cursor_pos = IVec2::new(cell_pos.x, (globals.img_size as i32) - cell_pos.y - 1);
-        return Some(cursor_pos);
+        let view = globals.view();
+        let pixel = (rel_cursor_position * view.sizes.as_vec2()).as_ivec2();
+        return Some(view.to_grid(pixel));
     }
     return None;
 }
@@ -17,13 +22,13 @@ pub fn get_out_img_touch_pos(window: &Window, touch: &Touch, globals: &GameGloba
     let window_size = Vec2::new(window.width(), window.height());
     let win_position = touch.position();
 
-    let min_pos = (window_size - Vec2::splat(globals.out_tex_size as f32)) * 0.5;
-    let max_pos = min_pos + Vec2::splat(globals.out_tex_size as f32);
+    let canvas_size = globals.canvas_size();
+    let min_pos = (window_size - canvas_size) * 0.5;
+    let max_pos = min_pos + canvas_size;
     if win_position.x >= min_pos.x && win_position.y >= min_pos.y && win_position.x <= max_pos.x && win_position.y <= max_pos.y {
-        let cell_size = (globals.out_tex_size / globals.img_size) as f32;
-        let cell_pos = ((win_position - min_pos) / cell_size).as_ivec2();
-        let cursor_pos = IVec2::new(cell_pos.x, (globals.img_size as i32) - cell_pos.y - 1);
-        return Some(cursor_pos);
+        let cell_size = globals.pixel_scale as f32;
+        let pixel = ((win_position - min_pos) / cell_size).as_ivec2();
+        return Some(globals.view().to_grid(pixel));
     }
     return None;
 }
@@ -33,6 +38,7 @@ pub fn update_input(
     mouse_button: Res<ButtonInput<MouseButton>>,
     keyboard_input: Res<ButtonInput<KeyCode>>,
     touches: Res<Touches>,
+    mut mouse_motion: EventReader<MouseMotion>,
     windows: Query<&Window, With<PrimaryWindow>>,
     relative_cursor_position_query: Query<&RelativeCursorPosition, With<DrawingCanvas>>,
 ) {
@@ -45,35 +51,40 @@ pub fn update_input(
     let relative_cursor_position = relative_cursor_position_query.single();
     //info!("Window size: {}x{}", window.width(), window.height());
 
-    if keyboard_input.pressed(KeyCode::Digit0) {
-        globals.place_cell_type = CellType::Air;
-    }
-    else if keyboard_input.pressed(KeyCode::Digit1) {
-        globals.place_cell_type = CellType::Sand;
-    }
-    else if keyboard_input.pressed(KeyCode::Digit2) {
-        globals.place_cell_type = CellType::Water;
-    }
-    else if keyboard_input.pressed(KeyCode::Digit3) {
-        globals.place_cell_type = CellType::Stone;
-    }
-    else if keyboard_input.pressed(KeyCode::Digit4) {
-        globals.place_cell_type = CellType::FlammableGass;
-    }
-    else if keyboard_input.pressed(KeyCode::Digit5) {
-        globals.place_cell_type = CellType::Oil;
-    }
-    else if keyboard_input.pressed(KeyCode::Digit6) {
-        globals.place_cell_type = CellType::Fire;
+    let digit_keys = [
+        KeyCode::Digit0, KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4,
+        KeyCode::Digit5, KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+    ];
+    if let Some(digit) = digit_keys.iter().position(|key| keyboard_input.pressed(*key)) {
+        if let Some(cell_type) = globals.grid.materials.with_hotkey(digit as u8) {
+            globals.place_cell_type = cell_type;
+        }
     }
-    else if keyboard_input.pressed(KeyCode::Digit7) {
-        globals.place_cell_type = CellType::Wood;
+
+    // tilts the box by 45 degrees
+    for (key, angle) in [(KeyCode::KeyQ, FRAC_PI_4), (KeyCode::KeyE, -FRAC_PI_4)] {
+        if keyboard_input.just_pressed(key) {
+            let gravity = Vec2::from_angle(angle).rotate(globals.grid.settings.gravity);
+            globals.grid.set_gravity(gravity);
+        }
     }
-    else if keyboard_input.pressed(KeyCode::Digit8) {
-        globals.place_cell_type = CellType::Acid;
+
+    // the view is dragged with the middle mouse button or moved with the arrows
+    let mouse_delta: Vec2 = mouse_motion.read().map(|motion| motion.delta).sum();
+    if globals.middle_pressed_on_canvas {
+        let delta = Vec2::new(-mouse_delta.x, mouse_delta.y) / globals.pixel_scale as f32;
+        globals.pan_view(delta);
+    }
+    let pan_speed = globals.view().sizes.as_vec2() * VIEW_PAN_SPEED;
+    for (key, dir) in [(KeyCode::ArrowLeft, -Vec2::X), (KeyCode::ArrowRight, Vec2::X), (KeyCode::ArrowDown, -Vec2::Y), (KeyCode::ArrowUp, Vec2::Y)] {
+        if keyboard_input.pressed(key) {
+            globals.pan_view(dir * pan_speed);
+        }
     }
-    else if keyboard_input.pressed(KeyCode::Digit9) {
-        globals.place_cell_type = CellType::Glass;
+    for (keys, step) in [([KeyCode::Equal, KeyCode::NumpadAdd], 1), ([KeyCode::Minus, KeyCode::NumpadSubtract], -1)] {
+        if keyboard_input.any_just_pressed(keys) {
+            globals.zoom_view(step);
+        }
     }
 
     let brush_type = globals.brush_type;
@@ -89,9 +100,15 @@ pub fn update_input(
     if mouse_button.just_pressed(MouseButton::Right) && mouse_over {
         globals.right_pressed_on_canvas = true;
     }
+    if mouse_button.just_pressed(MouseButton::Middle) && mouse_over {
+        globals.middle_pressed_on_canvas = true;
+    }
     if mouse_button.just_released(MouseButton::Left) {
         globals.left_pressed_on_canvas = false;
     }
+    if mouse_button.just_released(MouseButton::Middle) {
+        globals.middle_pressed_on_canvas = false;
+    }
     if mouse_button.just_released(MouseButton::Right) {
         globals.right_pressed_on_canvas = false;
     }
@@ -1,13 +1,38 @@
-mod utils;
-mod cell;
-mod cell_grid;
+// simulation core, builds without bevy
+pub mod utils;
+pub mod cell;
+pub mod cell_grid;
+pub mod gravity;
+pub mod boundaries;
+pub mod grid_chunks;
+pub mod materials;
+pub mod reactions;
+pub mod particles;
+pub mod rigid_bodies;
+pub mod structural_integrity;
+pub mod liquid_pressure;
+pub mod electricity;
+pub mod logic;
+pub mod plants;
+pub mod creatures;
+pub mod grid_config;
+pub mod color_settings;
+
+// bevy app
+#[cfg(feature = "app")]
+mod img_utils;
+#[cfg(feature = "app")]
 mod grid_display;
+#[cfg(feature = "app")]
 mod app;
+#[cfg(feature = "app")]
 mod ui;
+#[cfg(feature = "app")]
 mod input;
-mod grid_config;
+#[cfg(feature = "app")]
 mod ui_control;
+#[cfg(feature = "app")]
 mod brush_icons;
-mod color_settings;
 
-pub use app::*;
\ No newline at end of file
+#[cfg(feature = "app")]
+pub use app::*;
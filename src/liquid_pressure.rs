@@ -0,0 +1,282 @@
+use std::mem::{replace, take};
+
+use bevy_math::prelude::*;
+
+use crate::utils::*;
+use crate::cell_grid::*;
+use crate::particles::*;
+use crate::grid_chunks::*;
+
+const LIQUID_OFFSETS: [IVec2; 4] = [IVec2::new(1, 0), IVec2::new(0, 1), IVec2::new(-1, 0), IVec2::new(0, -1)];
+// fraction of the speed a jet would have without any losses
+const JET_DAMPING: f32 = 0.8;
+
+// buffers kept from one pass to the next, so the whole grid isn't allocated again every time
+pub struct LiquidPressureState
+{
+    // cells woken since the last pass in every chunk, only the bodies touching them are walked
+    dirty: Vec<DirtyRect>,
+    // grid the dirty rectangles belong to, a resize might keep the number of chunks
+    dirty_sizes: IVec2,
+    // the cells walked by the pass of the same number
+    visited: Vector2D<u32>,
+    slot_visited: Vector2D<u32>,
+    pass: u32,
+    body: Vec<IVec2>,
+    stack: Vec<IVec2>,
+    surface: Vec<IVec2>,
+    // free cells next to the body with the direction from the liquid
+    slots: Vec<(IVec2, IVec2)>,
+}
+
+impl Default for LiquidPressureState {
+    fn default() -> Self
+    {
+        LiquidPressureState {
+            dirty: Vec::new(),
+            dirty_sizes: IVec2::ZERO,
+            visited: Vector2D::new(IVec2::ZERO, 0),
+            slot_visited: Vector2D::new(IVec2::ZERO, 0),
+            pass: 0,
+            body: Vec::new(),
+            stack: Vec::new(),
+            surface: Vec::new(),
+            slots: Vec::new(),
+        }
+    }
+}
+
+impl LiquidPressureState {
+    fn include_dirty(&mut self, chunks: &GridChunks)
+    {
+        if self.dirty_sizes != chunks.grid_sizes || self.dirty.len() != chunks.count() {
+            self.dirty = vec![DirtyRect::empty(); chunks.count()];
+            self.dirty_sizes = chunks.grid_sizes;
+        }
+        for (dirty, chunk) in self.dirty.iter_mut().zip(chunks.chunks.data.iter()) {
+            if !chunk.current.is_empty() {
+                dirty.include(chunk.current.min, chunk.current.max);
+            }
+        }
+    }
+
+    // the visit marks of the last pass are left behind, they don't match the number of the new one
+    fn start_pass(&mut self, sizes: IVec2)
+    {
+        if self.visited.sizes != sizes {
+            self.visited = Vector2D::new(sizes, 0);
+            self.slot_visited = Vector2D::new(sizes, 0);
+        }
+        self.pass = self.pass.wrapping_add(1);
+        if self.pass == 0 {
+            // the old marks might match again, so they are cleared
+            self.visited.data.fill(0);
+            self.slot_visited.data.fill(0);
+            self.pass = 1;
+        }
+    }
+}
+
+impl CellGrid
+{
+    // the pressure of a connected body of liquid pushes its highest cells into the lowest free cells around it,
+    // so the levels of connected vessels even out
+    // only the bodies that changed since the last pass are walked, a settled pool is left alone,
+    // and the bodies end at the frozen chunks
+    pub fn update_liquid_pressure(&mut self)
+    {
+        if !self.settings.liquid_pressure {
+            return;
+        }
+        let mut state = take(&mut self.liquid_pressure_state);
+        state.include_dirty(&self.chunks);
+        if self.updates.is_multiple_of(self.settings.liquid_pressure_interval.max(1) as u64) {
+            self.apply_liquid_pressure(&mut state);
+        }
+        self.liquid_pressure_state = state;
+    }
+
+    fn apply_liquid_pressure(&mut self, state: &mut LiquidPressureState)
+    {
+        state.start_pass(self.cells.sizes);
+        let pass = state.pass;
+        let (active_min, active_max) = self.chunks.active_cells_rect();
+        let is_active = |pos: IVec2| pos.cmpge(active_min).all() && pos.cmple(active_max).all();
+        let LiquidPressureState { dirty, visited, slot_visited, body, stack, surface, slots, .. } = state;
+        for chunk_dirty in dirty.iter_mut() {
+            let rect = replace(chunk_dirty, DirtyRect::empty());
+            if rect.is_empty() {
+                continue;
+            }
+            for y in rect.min.y..=rect.max.y {
+                for x in rect.min.x..=rect.max.x {
+                    let start = IVec2::new(x, y);
+                    let liquid_type = self.cells[start].cell_type;
+                    // liquid creatures like the fish swim on their own instead of being pushed around
+                    let is_pushed = self.materials.is_liquid(liquid_type) && self.materials.creature(liquid_type).is_none();
+                    if visited[start] == pass || !is_active(start) || !is_pushed {
+                        continue;
+                    }
+                    visited[start] = pass;
+                    stack.push(start);
+                    body.clear();
+                    surface.clear();
+                    slots.clear();
+                    while let Some(pos) = stack.pop() {
+                        body.push(pos);
+                        for off in LIQUID_OFFSETS {
+                            let neighbor_pos = pos + off;
+                            if !self.cells.is_in_range(neighbor_pos) || !is_active(neighbor_pos) {
+                                continue;
+                            }
+                            let neighbor_type = self.cells[neighbor_pos].cell_type;
+                            if neighbor_type == liquid_type {
+                                if visited[neighbor_pos] != pass {
+                                    visited[neighbor_pos] = pass;
+                                    stack.push(neighbor_pos);
+                                }
+                            } else if self.materials.is_gass(neighbor_type) && slot_visited[neighbor_pos] != pass {
+                                slot_visited[neighbor_pos] = pass;
+                                slots.push((neighbor_pos, off));
+                            }
+                        }
+                        // without gravity nothing is pushed anywhere
+                        let Some(dirs) = self.settings.gravity_dirs_at(pos) else {
+                            continue;
+                        };
+                        let above_pos = pos - dirs.down;
+                        if !self.cells.is_in_range(above_pos) || self.cells[above_pos].cell_type != liquid_type {
+                            surface.push(pos);
+                        }
+                    }
+
+                    // the highest cells go first into the lowest slots, the heights follow the local gravity
+                    let height = |pos: &IVec2| self.settings.gravity_height(*pos);
+                    surface.sort_by(|a, b| height(b).total_cmp(&height(a)).then(a.x.cmp(&b.x)).then(a.y.cmp(&b.y)));
+                    slots.sort_by(|(a, _), (b, _)| height(a).total_cmp(&height(b)).then(a.x.cmp(&b.x)).then(a.y.cmp(&b.y)));
+                    let flow = self.settings.liquid_pressure_flow as usize;
+                    for (from_pos, (slot_pos, dir)) in surface.iter().zip(slots.iter()).take(flow) {
+                        let head = self.settings.gravity_height(*from_pos) - self.settings.gravity_height(*slot_pos);
+                        // a difference of one cell is evened out by the liquid flowing sideways
+                        if head < 2.0 {
+                            break;
+                        }
+                        self.push_liquid(*from_pos, *slot_pos, *dir, head);
+                    }
+                }
+            }
+        }
+    }
+
+    // moves the liquid cell into the slot, the gass from the slot takes its place
+    // under a high pressure the liquid shoots out of the slot
+    fn push_liquid(&mut self, from_pos: IVec2, slot_pos: IVec2, dir: IVec2, head: f32)
+    {
+        let liquid_cell = self.cells[from_pos];
+        self.cells[from_pos] = self.cells[slot_pos];
+        self.chunks.wake_cell(from_pos);
+        if head >= self.settings.jet_head {
+            let speed = JET_DAMPING * (2.0 * self.settings.particle_gravity * head).sqrt();
+            self.particles.push(Particle { cell: liquid_cell, pos: slot_pos.as_vec2() + 0.5, vel: dir.as_vec2() * speed });
+        } else {
+            self.cells[slot_pos] = liquid_cell;
+            self.chunks.wake_cell(slot_pos);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell::*;
+    use crate::boundaries::*;
+    use crate::grid_config::*;
+
+    // tall column of water in a tube hanging from the top wall, open at the bottom
+    // whatever is pushed down falls out of the grid through the void at the bottom
+    fn water_grid(gravity: Vec2) -> CellGrid
+    {
+        let mut grid = get_default_cell_grid(IVec2::new(64, 64), 3);
+        grid.settings.boundaries.top = Boundary::new(BoundaryType::Wall);
+        grid.settings.boundaries.bottom = Boundary::new(BoundaryType::Void);
+        grid.settings.gravity = gravity;
+        for x in [20, 44] {
+            grid.set_cells(IVec2::new(x, 20), Some(IVec2::new(x, 63)), BrushType::LineRound, 1, CellType::Stone, true);
+        }
+        for y in 22..63 {
+            grid.set_cells(IVec2::new(23, y), Some(IVec2::new(41, y)), BrushType::LineRound, 0, CellType::Water, false);
+        }
+        grid
+    }
+
+    fn water_count(grid: &CellGrid) -> usize
+    {
+        let in_cells = grid.cells.data.iter().filter(|cell| cell.cell_type == CellType::Water).count();
+        in_cells + grid.particles.iter().filter(|particle| particle.cell.cell_type == CellType::Water).count()
+    }
+
+    fn assert_water_kept(gravity: Vec2)
+    {
+        let mut grid = water_grid(gravity);
+        let water = water_count(&grid);
+        assert!(water > 0);
+        for update in 0..300 {
+            grid.update(false);
+            assert_eq!(water_count(&grid), water, "water lost at update {update}");
+        }
+    }
+
+    // top of the water in the columns
+    fn water_level(grid: &CellGrid, min_x: i32, max_x: i32) -> i32
+    {
+        (0..grid.cells.sizes.y).rev()
+            .find(|y| (min_x..=max_x).any(|x| grid.cells[IVec2::new(x, *y)].cell_type == CellType::Water))
+            .unwrap_or(-1)
+    }
+
+    #[test]
+    fn connected_vessels_even_out()
+    {
+        let mut grid = get_default_cell_grid(IVec2::new(64, 64), 7);
+        grid.settings.rigid_bodies = false;
+        // two arms joined by a channel at the bottom, the water is poured into the left one
+        grid.set_cells_square(IVec2::new(27, 20), 20, CellType::Stone, true);
+        grid.set_cells_square_line(IVec2::new(12, 40), IVec2::new(12, 4), 2, CellType::Air, true);
+        grid.set_cells_square_line(IVec2::new(42, 40), IVec2::new(42, 4), 2, CellType::Air, true);
+        grid.set_cells_square_line(IVec2::new(12, 4), IVec2::new(42, 4), 1, CellType::Air, true);
+        grid.set_cells_square_line(IVec2::new(12, 35), IVec2::new(12, 10), 2, CellType::Water, true);
+        for update in 0..1500 {
+            grid.update(update % 2 == 0);
+        }
+        let left = water_level(&grid, 10, 14);
+        let right = water_level(&grid, 40, 44);
+        assert!(right > 4 && (left - right).abs() <= 2, "levels {left} and {right}");
+    }
+
+    // the dirty cells of the old grid must not be walked after a resize keeping the number of chunks
+    #[test]
+    fn resize_drops_dirty_cells()
+    {
+        let mut grid = get_default_cell_grid(IVec2::new(100, 100), 7);
+        grid.set_cells(IVec2::new(95, 95), None, BrushType::Square, 3, CellType::Water, false);
+        for update in 0..2 {
+            grid.update(update % 2 == 0);
+        }
+        grid.resize(IVec2::new(97, 97));
+        for update in 0..4 {
+            grid.update(update % 2 == 0);
+        }
+    }
+
+    #[test]
+    fn zero_gravity_keeps_water()
+    {
+        assert_water_kept(Vec2::ZERO);
+    }
+
+    #[test]
+    fn flipped_gravity_keeps_water()
+    {
+        assert_water_kept(Vec2::new(0.0, 1.0));
+    }
+}
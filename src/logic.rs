@@ -0,0 +1,208 @@
+use bevy_math::prelude::*;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::cell::*;
+use crate::cell_grid::*;
+use crate::electricity::*;
+use crate::materials::*;
+use crate::reactions::*;
+
+// updates an input keeps counting as powered after its last pulse, the gap between the pulses of a steady current
+const INPUT_HOLD: u8 = FULL_CHARGE + 1;
+
+const SIDES: [IVec2; 2] = [IVec2::NEG_X, IVec2::X];
+const OUTPUTS: [IVec2; 2] = [IVec2::NEG_Y, IVec2::Y];
+const ORTHOGONAL: [IVec2; 4] = [IVec2::NEG_X, IVec2::X, IVec2::NEG_Y, IVec2::Y];
+
+// what a logic material does with the current, as written in data files
+// the gates and the delay take their inputs from the conductors at their sides and charge the ones above and below,
+// so their outputs don't feed back into the inputs
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Logic {
+    // charges the conductors around it while a matching cell touches it
+    Sensor(Reactant),
+    And,
+    Or,
+    Not,
+    // passes a pulse on after the number of updates, one pulse at a time
+    Delay(u8),
+    // turns into air with all the door cells connected to it while a neighbor powers it and comes back once the power is gone
+    Door,
+    // fills the free cells around it with the material while a neighbor powers it
+    Emitter { material: String, prob: f32 },
+}
+
+// logic with the material names resolved
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogicRule {
+    Sensor(CellMatcher),
+    And,
+    Or,
+    Not,
+    Delay(u8),
+    Door,
+    Emitter { cell_type: CellType, prob: f32 },
+}
+
+// connected door cells turned into air together, remembered so they can close again
+#[derive(Clone, Debug)]
+pub struct OpenDoor
+{
+    pub cells: Vec<IVec2>,
+    pub door_type: CellType,
+    // updates it stays open without power
+    pub hold: u8,
+}
+
+impl MaterialRegistry {
+    // has to run after all the materials are registered, like the phase changes
+    pub fn resolve_logic(&mut self) -> Result<(), String> {
+        let mut rules = Vec::with_capacity(self.len());
+        for material in self.materials.iter() {
+            let rule = match &material.logic {
+                None => None,
+                Some(Logic::Sensor(reactant)) => Some(LogicRule::Sensor(CellMatcher::new(reactant, self)?)),
+                Some(Logic::And) => Some(LogicRule::And),
+                Some(Logic::Or) => Some(LogicRule::Or),
+                Some(Logic::Not) => Some(LogicRule::Not),
+                Some(Logic::Delay(updates)) => Some(LogicRule::Delay((*updates).max(1))),
+                Some(Logic::Door) => Some(LogicRule::Door),
+                Some(Logic::Emitter { material: name, prob }) => {
+                    let cell_type = self.find(name).ok_or(format!("material {} emits unknown material {}", material.name, name))?;
+                    Some(LogicRule::Emitter { cell_type, prob: *prob })
+                },
+            };
+            rules.push(rule);
+        }
+        for (material, rule) in self.materials.iter_mut().zip(rules) {
+            material.logic_rule = rule;
+        }
+        Ok(())
+    }
+}
+
+impl CellGrid
+{
+    // a power source or a conductor the current has just reached
+    pub fn is_powering(&self, pos: IVec2) -> bool
+    {
+        if !self.cells.is_in_range(pos) {
+            return false;
+        }
+        let cell = &self.cells[pos];
+        self.materials.is_power_source(cell.cell_type) || (cell.charge == FULL_CHARGE && self.materials.is_conductive(cell.cell_type))
+    }
+
+    // pushes the positions to charge at the end of the update, the logic cell itself is among them when it's on,
+    // which keeps it awake
+    pub(crate) fn update_logic_cell(&mut self, pos: IVec2, rule: LogicRule, charged: &mut Vec<IVec2>)
+    {
+        let data = self.cells[pos].custom_data;
+        let outputs: &[IVec2] = match rule {
+            LogicRule::Sensor(matcher) => {
+                let touched = Neighborhood::Moore.offsets().iter().any(|off| {
+                    let neighbor_pos = pos + *off;
+                    self.cells.is_in_range(neighbor_pos) && matcher.matches_cell(&self.materials, &self.cells[neighbor_pos])
+                });
+                if touched { &ORTHOGONAL } else { &[] }
+            },
+            LogicRule::And | LogicRule::Or | LogicRule::Not => {
+                // the hold of the left input is in the low half of the data, the right one in the high half
+                let holds = [(IVec2::NEG_X, 0), (IVec2::X, 4)].map(|(side, shift)| {
+                    if self.is_powering(pos + side) { INPUT_HOLD } else { ((data >> shift) & 0xF).saturating_sub(1) }
+                });
+                self.cells[pos].custom_data = holds[0] | (holds[1] << 4);
+                let on = match rule {
+                    LogicRule::And => holds[0] > 0 && holds[1] > 0,
+                    LogicRule::Or => holds[0] > 0 || holds[1] > 0,
+                    _ => holds[0] == 0 && holds[1] == 0,
+                };
+                if on { &OUTPUTS } else { &[] }
+            },
+            LogicRule::Delay(updates) => {
+                // counts down from the delay after a pulse comes in and sends it on at zero
+                let mut fire = false;
+                if data > 0 {
+                    self.cells[pos].custom_data = data - 1;
+                    fire = data == 1;
+                } else if SIDES.iter().any(|side| self.is_powering(pos + *side)) {
+                    self.cells[pos].custom_data = updates;
+                }
+                if fire { &OUTPUTS } else { &[] }
+            },
+            LogicRule::Door => {
+                if ORTHOGONAL.iter().any(|off| self.is_powering(pos + *off)) {
+                    self.open_door(pos);
+                }
+                &[]
+            },
+            LogicRule::Emitter { cell_type, prob } => {
+                if ORTHOGONAL.iter().any(|off| self.is_powering(pos + *off)) {
+                    for off in ORTHOGONAL {
+                        let free_pos = pos + off;
+                        if self.cells.is_in_range(free_pos) && self.cells[free_pos].cell_type == CellType::Air && self.rng.random::<f32>() < prob {
+                            self.replace_cell(free_pos, cell_type);
+                        }
+                    }
+                    charged.push(pos);
+                }
+                &[]
+            },
+        };
+        // the changing inputs wake the cell, the holds and delays still counting down keep it awake themselves
+        if self.cells[pos].custom_data != 0 {
+            self.chunks.wake_rect(pos, pos);
+        }
+        if outputs.is_empty() {
+            return;
+        }
+        charged.push(pos);
+        for off in outputs {
+            let output_pos = pos + *off;
+            if self.cells.is_in_range(output_pos) && self.cells[output_pos].charge == 0 && self.materials.is_conductive(self.cells[output_pos].cell_type) {
+                charged.push(output_pos);
+            }
+        }
+    }
+
+    fn open_door(&mut self, pos: IVec2)
+    {
+        let door_type = self.cells[pos].cell_type;
+        let mut cells = Vec::new();
+        let mut stack = vec![pos];
+        self.replace_cell(pos, CellType::Air);
+        while let Some(pos) = stack.pop() {
+            cells.push(pos);
+            for off in ORTHOGONAL {
+                let neighbor_pos = pos + off;
+                if self.cells.is_in_range(neighbor_pos) && self.cells[neighbor_pos].cell_type == door_type {
+                    self.replace_cell(neighbor_pos, CellType::Air);
+                    stack.push(neighbor_pos);
+                }
+            }
+        }
+        self.open_doors.push(OpenDoor { cells, door_type, hold: INPUT_HOLD });
+    }
+
+    // the doors without power close again, crushing whatever moved into them unless it's a static solid
+    pub(crate) fn update_open_doors(&mut self)
+    {
+        let mut open_doors = std::mem::take(&mut self.open_doors);
+        open_doors.retain_mut(|door| {
+            let powered = door.cells.iter().any(|pos| ORTHOGONAL.iter().any(|off| self.is_powering(*pos + *off)));
+            door.hold = if powered { INPUT_HOLD } else { door.hold.saturating_sub(1) };
+            if door.hold > 0 {
+                return true;
+            }
+            for pos in door.cells.iter() {
+                if self.materials.phase(self.cells[*pos].cell_type) != CellPhase::StaticSolid {
+                    self.replace_cell(*pos, door.door_type);
+                }
+            }
+            false
+        });
+        open_doors.append(&mut self.open_doors);
+        self.open_doors = open_doors;
+    }
+}
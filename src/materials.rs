@@ -0,0 +1,377 @@
+use std::ops::{Index, IndexMut};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cell::*;
+use crate::color_settings::ColorSettings;
+use crate::logic::*;
+use crate::plants::*;
+use crate::creatures::*;
+
+// materials the simulation rules refer to directly, they have to be registered first and in this order
+pub const BUILT_IN_MATERIALS: [(CellType, &str); 15] = [
+    (CellType::Air, "Air"),
+    (CellType::Smoke, "Smoke"),
+    (CellType::FlammableGass, "FlammableGass"),
+    (CellType::Fire, "Fire"),
+    (CellType::Steam, "Steam"),
+    (CellType::Water, "Water"),
+    (CellType::Oil, "Oil"),
+    (CellType::Acid, "Acid"),
+    (CellType::Lava, "Lava"),
+    (CellType::Stone, "Stone"),
+    (CellType::Wood, "Wood"),
+    (CellType::Glass, "Glass"),
+    (CellType::Ice, "Ice"),
+    (CellType::Sand, "Sand"),
+    (CellType::Coal, "Coal"),
+];
+
+// classification of a material that doesn't depend on its phase, every flag owns its own bit
+// in data files the flags are written as a list of their names
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "Vec<String>", try_from = "Vec<String>")]
+pub struct MaterialFlags(u8);
+
+impl MaterialFlags {
+    pub const NONE: MaterialFlags = MaterialFlags(0);
+    // acid can eat through it
+    pub const DISSOLVABLE: MaterialFlags = MaterialFlags(1 << 0);
+    // carries the electric current
+    pub const CONDUCTIVE: MaterialFlags = MaterialFlags(1 << 1);
+    // keeps charging the conductors around it
+    pub const POWER_SOURCE: MaterialFlags = MaterialFlags(1 << 2);
+
+    pub const fn union(self, other: MaterialFlags) -> MaterialFlags {
+        MaterialFlags(self.0 | other.0)
+    }
+
+    pub const fn contains(self, other: MaterialFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const NAMES: [(&'static str, MaterialFlags); 3] = [
+        ("Dissolvable", MaterialFlags::DISSOLVABLE),
+        ("Conductive", MaterialFlags::CONDUCTIVE),
+        ("PowerSource", MaterialFlags::POWER_SOURCE),
+    ];
+}
+
+impl From<MaterialFlags> for Vec<String> {
+    fn from(flags: MaterialFlags) -> Self {
+        MaterialFlags::NAMES.iter().filter(|(_, flag)| flags.contains(*flag)).map(|(name, _)| String::from(*name)).collect()
+    }
+}
+
+impl TryFrom<Vec<String>> for MaterialFlags {
+    type Error = String;
+
+    fn try_from(names: Vec<String>) -> Result<Self, Self::Error> {
+        let mut flags = MaterialFlags::NONE;
+        for name in names {
+            match MaterialFlags::NAMES.iter().find(|(flag_name, _)| *flag_name == name) {
+                Some((_, flag)) => flags = flags.union(*flag),
+                None => return Err(format!("unknown material flag {}", name)),
+            }
+        }
+        Ok(flags)
+    }
+}
+
+// the material turns into another one when its temperature crosses the threshold
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PhaseChange
+{
+    pub temperature: f32,
+    pub into: String,
+    // resolved from the name once all the materials are registered
+    #[serde(skip)]
+    pub cell_type: CellType,
+}
+
+impl PhaseChange {
+    pub fn new(temperature: f32, into: &str) -> Self {
+        PhaseChange { temperature, into: String::from(into), cell_type: CellType::default() }
+    }
+}
+
+// what sets an explosive off
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Detonation {
+    // the flame of the burning explosive runs out
+    Burnout,
+    // the explosive falls onto a solid at least at the speed, in cells per update, it goes off on burnout too
+    Impact(f32),
+}
+
+// the material blows up instead of burning out, the blast destroys the solids weaker than its force
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Explosive
+{
+    pub detonation: Detonation,
+    pub radius: f32,
+    // force in the center, it decreases to zero at the radius
+    pub power: f32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Material
+{
+    // unique, used to refer to the material outside of the code
+    pub name: String,
+    // shown on the material button, the name when empty
+    #[serde(default)]
+    pub label: String,
+    pub phase: CellPhase,
+    #[serde(default)]
+    pub flags: MaterialFlags,
+    // number key that selects the material
+    #[serde(default)]
+    pub hotkey: Option<u8>,
+    pub properties: CellTypeProperties,
+    // melting or boiling, happens above the temperature
+    #[serde(default)]
+    pub heating: Option<PhaseChange>,
+    // freezing or condensing, happens below the temperature
+    #[serde(default)]
+    pub cooling: Option<PhaseChange>,
+    // powder a static solid cracks into when it can't hold itself up, it falls as it is without one
+    #[serde(default)]
+    pub cracked: Option<String>,
+    // resolved from the name once all the materials are registered
+    #[serde(skip)]
+    pub cracked_type: Option<CellType>,
+    // makes the material a sensor, gate or other part of circuits
+    #[serde(default)]
+    pub logic: Option<Logic>,
+    #[serde(skip)]
+    pub logic_rule: Option<LogicRule>,
+    // makes the material a part of a growing plant
+    #[serde(default)]
+    pub plant: Option<Plant>,
+    #[serde(skip)]
+    pub plant_rule: Option<PlantRule>,
+    // makes the material a moving creature
+    #[serde(default)]
+    pub creature: Option<Creature>,
+    #[serde(skip)]
+    pub creature_rule: Option<CreatureRule>,
+    #[serde(default)]
+    pub explosive: Option<Explosive>,
+    // colors used when a palette doesn't specify the material, taken from the properties on registration
+    #[serde(skip)]
+    pub base_colors: CellColors,
+}
+
+impl Material {
+    pub fn new(name: &str, phase: CellPhase, flags: MaterialFlags, properties: CellTypeProperties) -> Self {
+        Material {
+            name: String::from(name),
+            label: String::from(name),
+            phase,
+            flags,
+            hotkey: None,
+            properties,
+            heating: None,
+            cooling: None,
+            cracked: None,
+            cracked_type: None,
+            logic: None,
+            logic_rule: None,
+            plant: None,
+            plant_rule: None,
+            creature: None,
+            creature_rule: None,
+            explosive: None,
+            base_colors: CellColors::default(),
+        }
+    }
+
+    pub fn with_label(mut self, label: &str) -> Self {
+        self.label = String::from(label);
+        self
+    }
+
+    pub fn with_hotkey(mut self, hotkey: u8) -> Self {
+        self.hotkey = Some(hotkey);
+        self
+    }
+
+    pub fn with_heating(mut self, temperature: f32, into: &str) -> Self {
+        self.heating = Some(PhaseChange::new(temperature, into));
+        self
+    }
+
+    pub fn with_cooling(mut self, temperature: f32, into: &str) -> Self {
+        self.cooling = Some(PhaseChange::new(temperature, into));
+        self
+    }
+
+    pub fn with_cracked(mut self, into: &str) -> Self {
+        self.cracked = Some(String::from(into));
+        self
+    }
+
+    pub fn with_logic(mut self, logic: Logic) -> Self {
+        self.logic = Some(logic);
+        self
+    }
+
+    pub fn with_plant(mut self, plant: Plant) -> Self {
+        self.plant = Some(plant);
+        self
+    }
+
+    pub fn with_creature(mut self, creature: Creature) -> Self {
+        self.creature = Some(creature);
+        self
+    }
+
+    pub fn with_explosive(mut self, detonation: Detonation, radius: f32, power: f32) -> Self {
+        self.explosive = Some(Explosive { detonation, radius, power });
+        self
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct MaterialRegistry
+{
+    pub materials: Vec<Material>,
+    // materials that get a button in the UI, in the order of the buttons
+    pub buttons: Vec<CellType>,
+}
+
+impl MaterialRegistry {
+    pub fn new() -> Self {
+        MaterialRegistry::default()
+    }
+
+    pub fn register(&mut self, mut material: Material) -> CellType {
+        assert!(self.materials.len() <= u8::MAX as usize, "too many materials");
+        assert!(self.find(&material.name).is_none(), "material {} is already registered", material.name);
+        if material.label.is_empty() {
+            material.label = material.name.clone();
+        }
+        material.base_colors = material.properties.colors.clone();
+        self.materials.push(material);
+        CellType((self.materials.len() - 1) as u8)
+    }
+
+    pub fn add_button(&mut self, cell_type: CellType) {
+        self.buttons.push(cell_type);
+    }
+
+    pub fn len(&self) -> usize {
+        self.materials.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.materials.is_empty()
+    }
+
+    pub fn types(&self) -> impl Iterator<Item = CellType> {
+        (0..self.materials.len()).map(|i| CellType(i as u8))
+    }
+
+    pub fn get(&self, cell_type: CellType) -> &Material {
+        &self.materials[cell_type.index()]
+    }
+
+    pub fn find(&self, name: &str) -> Option<CellType> {
+        self.materials.iter().position(|material| material.name == name).map(|i| CellType(i as u8))
+    }
+
+    pub fn with_hotkey(&self, hotkey: u8) -> Option<CellType> {
+        self.materials.iter().position(|material| material.hotkey == Some(hotkey)).map(|i| CellType(i as u8))
+    }
+
+    pub fn phase(&self, cell_type: CellType) -> CellPhase {
+        self.get(cell_type).phase
+    }
+
+    pub fn is_solid(&self, cell_type: CellType) -> bool {
+        self.phase(cell_type).is_solid()
+    }
+
+    pub fn is_liquid(&self, cell_type: CellType) -> bool {
+        self.phase(cell_type) == CellPhase::Liquid
+    }
+
+    pub fn is_gass(&self, cell_type: CellType) -> bool {
+        self.phase(cell_type) == CellPhase::Gass
+    }
+
+    pub fn is_powder(&self, cell_type: CellType) -> bool {
+        self.phase(cell_type) == CellPhase::Powder
+    }
+
+    pub fn is_static_solid(&self, cell_type: CellType) -> bool {
+        self.phase(cell_type) == CellPhase::StaticSolid
+    }
+
+    pub fn is_dissolvable(&self, cell_type: CellType) -> bool {
+        self.get(cell_type).flags.contains(MaterialFlags::DISSOLVABLE)
+    }
+
+    pub fn is_conductive(&self, cell_type: CellType) -> bool {
+        self.get(cell_type).flags.contains(MaterialFlags::CONDUCTIVE)
+    }
+
+    pub fn is_power_source(&self, cell_type: CellType) -> bool {
+        self.get(cell_type).flags.contains(MaterialFlags::POWER_SOURCE)
+    }
+
+    pub fn logic(&self, cell_type: CellType) -> Option<LogicRule> {
+        self.get(cell_type).logic_rule
+    }
+
+    pub fn plant(&self, cell_type: CellType) -> Option<PlantRule> {
+        self.get(cell_type).plant_rule
+    }
+
+    pub fn creature(&self, cell_type: CellType) -> Option<CreatureRule> {
+        self.get(cell_type).creature_rule
+    }
+
+    pub fn explosive(&self, cell_type: CellType) -> Option<Explosive> {
+        self.get(cell_type).explosive
+    }
+
+    // phase changes and cracking refer to materials by name, so they can only be resolved once all of them are registered
+    pub fn resolve_phase_changes(&mut self) -> Result<(), String> {
+        let names: Vec<String> = self.materials.iter().map(|material| material.name.clone()).collect();
+        for material in self.materials.iter_mut() {
+            for change in [&mut material.heating, &mut material.cooling].into_iter().flatten() {
+                let index = names.iter().position(|name| *name == change.into)
+                    .ok_or(format!("material {} changes into unknown material {}", material.name, change.into))?;
+                change.cell_type = CellType(index as u8);
+            }
+            if let Some(cracked) = &material.cracked {
+                let index = names.iter().position(|name| name == cracked)
+                    .ok_or(format!("material {} cracks into unknown material {}", material.name, cracked))?;
+                material.cracked_type = Some(CellType(index as u8));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set_palette(&mut self, palette: &ColorSettings) {
+        for (i, material) in self.materials.iter_mut().enumerate() {
+            material.properties.colors = palette.get(&CellType(i as u8)).unwrap_or(&material.base_colors).clone();
+        }
+    }
+}
+
+impl Index<CellType> for MaterialRegistry {
+    type Output = CellTypeProperties;
+
+    fn index(&self, cell_type: CellType) -> &Self::Output {
+        &self.materials[cell_type.index()].properties
+    }
+}
+
+impl IndexMut<CellType> for MaterialRegistry {
+    fn index_mut(&mut self, cell_type: CellType) -> &mut Self::Output {
+        &mut self.materials[cell_type.index()].properties
+    }
+}
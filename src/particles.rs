@@ -0,0 +1,157 @@
+use std::mem::take;
+
+use bevy_math::prelude::*;
+use rand::prelude::*;
+
+use crate::utils::*;
+use crate::cell::*;
+use crate::cell_grid::*;
+use crate::boundaries::*;
+
+// cell lifted out of the grid, it flies until it hits something and is deposited back
+#[derive(Clone, Copy)]
+pub struct Particle
+{
+    pub cell: Cell,
+    // in cells, the center of the cell (x, y) is at (x + 0.5, y + 0.5)
+    pub pos: Vec2,
+    // cells per update
+    pub vel: Vec2,
+}
+
+impl Particle {
+    pub fn cell_pos(&self) -> IVec2 {
+        self.pos.floor().as_ivec2()
+    }
+}
+
+impl CellGrid
+{
+    // gasses can't be thrown, they only get blown around
+    pub fn launch_cell(&mut self, pos: IVec2, vel: Vec2) -> bool
+    {
+        let cell = self.cells[pos];
+        if self.materials.is_gass(cell.cell_type) {
+            return false;
+        }
+        self.replace_cell(pos, CellType::Air);
+        self.particles.push(Particle { cell, pos: pos.as_vec2() + 0.5, vel });
+        true
+    }
+
+    // throws the cells in the circle along the line from its center, static solids only when they can be replaced
+    pub fn fling_cells(&mut self, pos_from: IVec2, pos_to: IVec2, size: i32, replace_solids: bool)
+    {
+        let vel = (pos_to - pos_from).as_vec2() * self.settings.fling_speed;
+        let mut positions = Vec::new();
+        let mut add_pos = |pos: IVec2| {
+            if self.cells.is_in_range(pos) && (replace_solids || self.materials.phase(self.cells[pos].cell_type) != CellPhase::StaticSolid) {
+                positions.push(pos);
+            }
+        };
+
+        bresenham_circle_fill(pos_from, size, &mut add_pos);
+
+        positions.sort_by_key(|pos| (pos.y, pos.x));
+        positions.dedup();
+        for pos in positions {
+            self.launch_cell(pos, vel);
+        }
+    }
+
+    pub fn update_particles(&mut self)
+    {
+        for mut particle in take(&mut self.particles) {
+            // the particles above the frozen chunks wait until those are active again
+            if !self.chunks.is_active_cell(particle.cell_pos()) {
+                self.particles.push(particle);
+                continue;
+            }
+            particle.vel += self.settings.gravity_at(particle.pos) * self.settings.particle_gravity;
+            particle.vel = particle.vel.clamp_length_max(self.settings.max_particle_speed);
+            // steps of at most one cell, so no cell is skipped
+            let steps = particle.vel.abs().max_element().ceil().max(1.0) as i32;
+            let mut start_pos = particle.pos;
+            let mut last_pos = particle.cell_pos();
+            let mut hit = None;
+            let mut lost = false;
+            for step in 1..=steps {
+                let mut pos = start_pos + particle.vel * (step as f32 / steps as f32);
+                let mut cell_pos = pos.floor().as_ivec2();
+                if cell_pos != last_pos {
+                    // the edges wrap the particles, swallow them or stop them like any other solid
+                    match self.settings.boundaries.move_target(cell_pos, self.cells.sizes) {
+                        MoveTarget::Cell(wrapped_pos) => {
+                            let shift = (wrapped_pos - cell_pos).as_vec2();
+                            start_pos += shift;
+                            pos += shift;
+                            cell_pos = wrapped_pos;
+                        },
+                        MoveTarget::Void => {
+                            lost = true;
+                            break;
+                        },
+                        MoveTarget::Blocked => {
+                            hit = Some(cell_pos);
+                            break;
+                        },
+                    }
+                    // particles fly through gasses
+                    if !self.materials.is_gass(self.cells[cell_pos].cell_type) {
+                        hit = Some(cell_pos);
+                        break;
+                    }
+                    last_pos = cell_pos;
+                }
+                particle.pos = pos;
+            }
+            match hit {
+                _ if lost => {},
+                Some(hit_pos) => self.deposit_particle(particle, last_pos, hit_pos),
+                None => self.particles.push(particle),
+            }
+        }
+    }
+
+    fn deposit_particle(&mut self, particle: Particle, pos: IVec2, hit_pos: IVec2)
+    {
+        // fast particles splash the lighter liquid they fall into and take its place
+        let speed = particle.vel.length();
+        if speed >= self.settings.splash_speed && self.cells.is_in_range(hit_pos) && self.materials.is_liquid(self.cells[hit_pos].cell_type)
+            && self.materials[particle.cell.cell_type].density > self.materials[self.cells[hit_pos].cell_type].density {
+            for off in [IVec2::new(0, 0), IVec2::new(-1, 0), IVec2::new(1, 0)] {
+                let splash_pos = hit_pos + off;
+                if !self.cells.is_in_range(splash_pos) || !self.materials.is_liquid(self.cells[splash_pos].cell_type) {
+                    continue;
+                }
+                let side = if off.x == 0 { self.rng.random_range(-1.0..=1.0) } else { off.x as f32 };
+                let vel = 0.5 * speed * Vec2::new(side * self.rng.random_range(0.2..0.6), 1.0);
+                self.launch_cell(splash_pos, vel);
+            }
+            self.place_particle(particle, hit_pos);
+            return;
+        }
+        self.place_particle(particle, pos);
+    }
+
+    // into the first gass cell against the gravity, or along it when the way up is full, like under a ceiling
+    // the particle is lost only when there is none at all
+    fn place_particle(&mut self, particle: Particle, pos: IVec2)
+    {
+        let start_pos = pos.clamp(IVec2::ZERO, self.cells.sizes - 1);
+        let up = self.settings.gravity_dirs_at(start_pos).map_or(IVec2::Y, |dirs| -dirs.down);
+        for dir in [up, -up] {
+            let mut pos = start_pos;
+            while self.cells.is_in_range(pos) {
+                if self.materials.is_gass(self.cells[pos].cell_type) {
+                    self.cells[pos] = particle.cell;
+                    self.chunks.wake_cell(pos);
+                    // the landed cell is a new load or even a new solid
+                    self.chunks.mark_solids_changed();
+                    return;
+                }
+                pos += dir;
+            }
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use bevy_math::prelude::*;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::cell::*;
+use crate::cell_grid::*;
+use crate::materials::*;
+use crate::reactions::*;
+use crate::utils::*;
+
+// water the plant cells hold in the low bits of the custom data, in sap units
+const SAP_MASK: u8 = 0x3F;
+const MAX_SAP: u8 = 32;
+const SAP_PER_WATER: u8 = 8;
+// sap a new plant cell costs, the seed starts with the sap of one water
+const GROWTH_SAP: u8 = 2;
+// the high bits hold where a stem cell grows, straight in the direction of the stem or to one of its sides
+const HEADING_SHIFT: u8 = 6;
+const STRAIGHT: u8 = 0;
+const LEFT: u8 = 1;
+const RIGHT: u8 = 2;
+// chance per cell of a branch that it turns to grow straight
+const BRANCH_TURN_PROB: f32 = 0.3;
+
+const ORTHOGONAL: [IVec2; 4] = [IVec2::NEG_X, IVec2::X, IVec2::NEG_Y, IVec2::Y];
+
+// how a plant material grows, as written in data files
+// the plant parts grow and pass the sap on when their timer runs out, so the timer of a burning part is short
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Plant {
+    // sprouts into the stem when resting on the soil next to water, the soil below it turns into the root
+    Seed { soil: String, stem: String, root: String },
+    // grows into the air in the direction and branches off to both sides,
+    // the probabilities are taken per cell from its position so every plant gets its own shape
+    Stem { dir: IVec2, branch_prob: f32, stop_prob: f32 },
+    // drinks the water around it and grows down through the soil
+    Root { soil: String, stop_prob: f32 },
+}
+
+// plant with the material names resolved
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlantRule {
+    Seed { soil: CellType, stem: CellType, root: CellType },
+    Stem { dir: IVec2, branch_prob: f32, stop_prob: f32 },
+    Root { soil: CellType, stop_prob: f32 },
+}
+
+impl MaterialRegistry {
+    // has to run after all the materials are registered, like the phase changes
+    pub fn resolve_plants(&mut self) -> Result<(), String> {
+        let mut rules = Vec::with_capacity(self.len());
+        for material in self.materials.iter() {
+            let find = |name: &String| self.find(name).ok_or(format!("plant {} refers to unknown material {}", material.name, name));
+            let rule = match &material.plant {
+                None => None,
+                Some(Plant::Seed { soil, stem, root }) => Some(PlantRule::Seed { soil: find(soil)?, stem: find(stem)?, root: find(root)? }),
+                Some(Plant::Stem { dir, branch_prob, stop_prob }) => Some(PlantRule::Stem { dir: *dir, branch_prob: *branch_prob, stop_prob: *stop_prob }),
+                Some(Plant::Root { soil, stop_prob }) => Some(PlantRule::Root { soil: find(soil)?, stop_prob: *stop_prob }),
+            };
+            rules.push(rule);
+        }
+        for (material, rule) in self.materials.iter_mut().zip(rules) {
+            material.plant_rule = rule;
+        }
+        Ok(())
+    }
+}
+
+// value in [0, 1) fixed for the position, the salt tells the different decisions apart
+fn pos_chance(pos: IVec2, salt: u32) -> f32
+{
+    let r = rand_from_pos_u32(pos.as_uvec2()) >> (salt * 8);
+    (r & 0xFF) as f32 / 256.0
+}
+
+impl CellGrid
+{
+    // only the cells woken for this frame grow, a plant cell stays awake while its timer runs
+    // and falls asleep after a growth step that changed nothing, until the sap or a neighbor changes
+    pub fn update_plants(&mut self)
+    {
+        if !self.settings.plants {
+            return;
+        }
+        for rect in self.chunks.current_rects() {
+            for y in rect.min.y..=rect.max.y {
+                for x in rect.min.x..=rect.max.x {
+                    self.update_plant_cell(IVec2::new(x, y));
+                }
+            }
+        }
+    }
+
+    fn update_plant_cell(&mut self, pos: IVec2)
+    {
+        let cell = self.cells[pos];
+        let Some(rule) = self.materials.plant(cell.cell_type) else {
+            return;
+        };
+        // a burning plant part is kept awake by its fire
+        if cell.is_on_fire() {
+            return;
+        }
+        if self.rng.random::<f32>() >= self.settings.plant_growth_prob {
+            self.chunks.wake_rect(pos, pos);
+            return;
+        }
+        // the timer stays above zero, so the flame of a burning plant part never starts out
+        if cell.timer > 1 {
+            self.cells[pos].timer -= 1;
+            self.chunks.wake_rect(pos, pos);
+            return;
+        }
+        self.cells[pos].set_timer(self.materials[cell.cell_type].timer);
+        match rule {
+            PlantRule::Seed { soil, stem, root } => self.sprout(pos, soil, stem, root),
+            PlantRule::Stem { dir, branch_prob, stop_prob } => {
+                self.grow_stem(pos, self.settings.gravity_turn_at(pos, dir), branch_prob, stop_prob);
+                self.share_sap(pos);
+            },
+            PlantRule::Root { soil, stop_prob } => {
+                self.drink(pos);
+                self.grow_root(pos, soil, stop_prob);
+                self.share_sap(pos);
+            },
+        }
+    }
+
+    fn is_plant(&self, pos: IVec2) -> bool
+    {
+        self.cells.is_in_range(pos) && self.materials.plant(self.cells[pos].cell_type).is_some()
+    }
+
+    fn sap(&self, pos: IVec2) -> u8
+    {
+        self.cells[pos].custom_data & SAP_MASK
+    }
+
+    // the plant cells next to it might grow or take the sap now
+    fn set_sap(&mut self, pos: IVec2, sap: u8)
+    {
+        let cell = &mut self.cells[pos];
+        cell.custom_data = (cell.custom_data & !SAP_MASK) | sap;
+        self.chunks.wake_cell(pos);
+    }
+
+    fn sprout(&mut self, pos: IVec2, soil: CellType, stem: CellType, root: CellType)
+    {
+        let soil_pos = pos + self.settings.gravity_turn_at(pos, IVec2::NEG_Y);
+        if !self.cells.is_in_range(soil_pos) || self.cells[soil_pos].cell_type != soil {
+            return;
+        }
+        let is_wet = [pos, soil_pos].iter().any(|center| Neighborhood::Moore.offsets().iter().any(|off| {
+            let neighbor_pos = *center + *off;
+            self.cells.is_in_range(neighbor_pos) && self.cells[neighbor_pos].cell_type == CellType::Water
+        }));
+        if !is_wet {
+            return;
+        }
+        self.replace_cell(pos, stem);
+        self.set_sap(pos, SAP_PER_WATER);
+        self.replace_cell(soil_pos, root);
+    }
+
+    // a new plant cell only grows where it touches no other plant cell than its parent, so the branches stay apart
+    fn grow_into(&mut self, pos: IVec2, target: IVec2, into_type: CellType, heading: u8) -> bool
+    {
+        if self.sap(pos) < GROWTH_SAP || !self.cells.is_in_range(target) || self.cells[target].cell_type != into_type {
+            return false;
+        }
+        if ORTHOGONAL.iter().any(|off| target + *off != pos && self.is_plant(target + *off)) {
+            return false;
+        }
+        self.set_sap(pos, self.sap(pos) - GROWTH_SAP);
+        self.replace_cell(target, self.cells[pos].cell_type);
+        self.cells[target].custom_data = heading << HEADING_SHIFT;
+        true
+    }
+
+    // the straight stem branches off to the side chosen by the position,
+    // the branches grow sideways until they turn in the direction of the stem and go on as straight stems
+    fn grow_stem(&mut self, pos: IVec2, dir: IVec2, branch_prob: f32, stop_prob: f32)
+    {
+        let heading = self.cells[pos].custom_data >> HEADING_SHIFT;
+        let side = |heading: u8| if heading == LEFT { dir.perp() } else { -dir.perp() };
+        if heading == STRAIGHT {
+            if pos_chance(pos, 1) < branch_prob {
+                let branch_heading = if pos_chance(pos, 2) < 0.5 { LEFT } else { RIGHT };
+                self.grow_into(pos, pos + side(branch_heading), CellType::Air, branch_heading);
+            }
+        } else if pos_chance(pos, 1) >= BRANCH_TURN_PROB {
+            let ahead = pos + side(heading);
+            if pos_chance(ahead, 0) >= stop_prob {
+                self.grow_into(pos, ahead, CellType::Air, heading);
+            }
+            return;
+        }
+        let ahead = pos + dir;
+        if pos_chance(ahead, 0) >= stop_prob {
+            self.grow_into(pos, ahead, CellType::Air, STRAIGHT);
+        }
+    }
+
+    fn grow_root(&mut self, pos: IVec2, soil: CellType, stop_prob: f32)
+    {
+        let down = self.settings.gravity_turn_at(pos, IVec2::NEG_Y);
+        let side = [-down.perp(), IVec2::ZERO, down.perp()][(rand_from_pos_u32(pos.as_uvec2()) % 3) as usize];
+        let target = pos + down + side;
+        if pos_chance(target, 0) >= stop_prob {
+            self.grow_into(pos, target, soil, STRAIGHT);
+        }
+    }
+
+    fn drink(&mut self, pos: IVec2)
+    {
+        for off in Neighborhood::Moore.offsets() {
+            let neighbor_pos = pos + *off;
+            if self.sap(pos) + SAP_PER_WATER > MAX_SAP {
+                return;
+            }
+            if self.cells.is_in_range(neighbor_pos) && self.cells[neighbor_pos].cell_type == CellType::Water {
+                self.replace_cell(neighbor_pos, CellType::Air);
+                self.set_sap(pos, self.sap(pos) + SAP_PER_WATER);
+            }
+        }
+    }
+
+    // sap flows to the drier neighboring plant cells one unit at a time
+    fn share_sap(&mut self, pos: IVec2)
+    {
+        for off in ORTHOGONAL {
+            let neighbor_pos = pos + off;
+            if self.is_plant(neighbor_pos) && self.sap(neighbor_pos) + 1 < self.sap(pos) {
+                self.set_sap(neighbor_pos, self.sap(neighbor_pos) + 1);
+                self.set_sap(pos, self.sap(pos) - 1);
+            }
+        }
+    }
+}
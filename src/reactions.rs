@@ -0,0 +1,269 @@
+use bevy_math::prelude::*;
+use enum_map::Enum;
+use serde::{Deserialize, Serialize};
+
+use crate::cell::*;
+use crate::materials::*;
+
+const MOORE_OFFSETS: [IVec2; 8] = [
+    IVec2::new(-1, -1), IVec2::new(0, -1), IVec2::new(1, -1),
+    IVec2::new(-1,  0),                    IVec2::new(1,  0),
+    IVec2::new(-1,  1), IVec2::new(0,  1), IVec2::new(1,  1),
+];
+const VON_NEUMANN_OFFSETS: [IVec2; 4] = [IVec2::new(0, -1), IVec2::new(-1, 0), IVec2::new(1, 0), IVec2::new(0, 1)];
+const BELOW_OFFSETS: [IVec2; 5] = [IVec2::new(0, -1), IVec2::new(-1, 0), IVec2::new(1, 0), IVec2::new(-1, -1), IVec2::new(1, -1)];
+const UP_OFFSETS: [IVec2; 1] = [IVec2::new(0, 1)];
+const DOWN_OFFSETS: [IVec2; 1] = [IVec2::new(0, -1)];
+
+// cells around the reacting cell where its partner is looked for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Enum, Serialize, Deserialize)]
+pub enum Neighborhood {
+    // all 8 surrounding cells
+    #[default]
+    Moore,
+    // the 4 orthogonal neighbors
+    VonNeumann,
+    // the cells at the sides and below, where a liquid touches things
+    Below,
+    Up,
+    Down,
+}
+
+impl Neighborhood {
+    pub fn offsets(self) -> &'static [IVec2] {
+        match self {
+            Neighborhood::Moore => &MOORE_OFFSETS,
+            Neighborhood::VonNeumann => &VON_NEUMANN_OFFSETS,
+            Neighborhood::Below => &BELOW_OFFSETS,
+            Neighborhood::Up => &UP_OFFSETS,
+            Neighborhood::Down => &DOWN_OFFSETS,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReactionTrigger {
+    // checked every update of the reacting cell
+    #[default]
+    Contact,
+    // checked when a burning cell spreads flames into its neighbor, the neighbor's ignite_prob applies first
+    Ignition,
+}
+
+// what a cell has to be to take part in a reaction, as written in data files
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Reactant {
+    Any,
+    Material(String),
+    Phase(CellPhase),
+    // powders and static solids
+    Solid,
+    // materials having all of the flags
+    Flags(MaterialFlags),
+    // cells that are on fire
+    Burning,
+    // grid edge that the cells can't leave through
+    Wall,
+}
+
+impl Reactant {
+    pub fn material(name: &str) -> Self {
+        Reactant::Material(String::from(name))
+    }
+}
+
+// reactant with the material names resolved
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CellMatcher {
+    Any,
+    Material(CellType),
+    Phase(CellPhase),
+    Solid,
+    Flags(MaterialFlags),
+    Burning,
+    Wall,
+}
+
+impl CellMatcher {
+    pub(crate) fn new(reactant: &Reactant, materials: &MaterialRegistry) -> Result<Self, String> {
+        Ok(match reactant {
+            Reactant::Any => CellMatcher::Any,
+            Reactant::Material(name) => CellMatcher::Material(find_material(name, materials)?),
+            Reactant::Phase(phase) => CellMatcher::Phase(*phase),
+            Reactant::Solid => CellMatcher::Solid,
+            Reactant::Flags(flags) => CellMatcher::Flags(*flags),
+            Reactant::Burning => CellMatcher::Burning,
+            Reactant::Wall => CellMatcher::Wall,
+        })
+    }
+
+    // whether cells of the material can match, burning can't be told from the material alone so it always can
+    pub fn matches_type(&self, materials: &MaterialRegistry, cell_type: CellType) -> bool {
+        match self {
+            CellMatcher::Any => true,
+            CellMatcher::Material(material) => *material == cell_type,
+            CellMatcher::Phase(phase) => materials.phase(cell_type) == *phase,
+            CellMatcher::Solid => materials.is_solid(cell_type),
+            CellMatcher::Flags(flags) => materials.get(cell_type).flags.contains(*flags),
+            CellMatcher::Burning => true,
+            CellMatcher::Wall => false,
+        }
+    }
+
+    pub fn matches_cell(&self, materials: &MaterialRegistry, cell: &Cell) -> bool {
+        match self {
+            CellMatcher::Burning => cell.is_on_fire(),
+            _ => self.matches_type(materials, cell.cell_type),
+        }
+    }
+}
+
+fn find_material(name: &str, materials: &MaterialRegistry) -> Result<CellType, String> {
+    materials.find(name).ok_or(format!("reaction with unknown material {}", name))
+}
+
+fn default_multiplier() -> f32 {
+    1.0
+}
+
+// a cell touching a neighbor in the neighborhood turns into the products with the probability
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Reaction
+{
+    #[serde(default)]
+    pub trigger: ReactionTrigger,
+    pub cell: Reactant,
+    pub neighbor: Reactant,
+    #[serde(default)]
+    pub neighborhood: Neighborhood,
+    pub prob: f32,
+    // the probability is multiplied when the catalyst is in the neighborhood too
+    #[serde(default)]
+    pub catalyst: Option<Reactant>,
+    #[serde(default = "default_multiplier")]
+    pub catalyst_multiplier: f32,
+    // the cells stay as they are without products
+    #[serde(default)]
+    pub cell_product: Option<String>,
+    #[serde(default)]
+    pub neighbor_product: Option<String>,
+    // placed into an empty cell of the neighborhood, if there is one
+    #[serde(default)]
+    pub byproduct: Option<String>,
+}
+
+impl Reaction {
+    pub fn contact(cell: Reactant, neighbor: Reactant, neighborhood: Neighborhood, prob: f32) -> Self {
+        Reaction {
+            trigger: ReactionTrigger::Contact,
+            cell,
+            neighbor,
+            neighborhood,
+            prob,
+            catalyst: None,
+            catalyst_multiplier: 1.0,
+            cell_product: None,
+            neighbor_product: None,
+            byproduct: None,
+        }
+    }
+
+    pub fn ignition(cell: Reactant, neighbor: Reactant) -> Self {
+        Reaction { trigger: ReactionTrigger::Ignition, ..Reaction::contact(cell, neighbor, Neighborhood::Moore, 1.0) }
+    }
+
+    pub fn with_cell_product(mut self, product: &str) -> Self {
+        self.cell_product = Some(String::from(product));
+        self
+    }
+
+    pub fn with_neighbor_product(mut self, product: &str) -> Self {
+        self.neighbor_product = Some(String::from(product));
+        self
+    }
+
+    pub fn with_byproduct(mut self, product: &str) -> Self {
+        self.byproduct = Some(String::from(product));
+        self
+    }
+
+    pub fn with_catalyst(mut self, catalyst: Reactant, multiplier: f32) -> Self {
+        self.catalyst = Some(catalyst);
+        self.catalyst_multiplier = multiplier;
+        self
+    }
+}
+
+// reaction with the material names resolved
+#[derive(Clone, Debug)]
+pub struct ReactionRule
+{
+    pub trigger: ReactionTrigger,
+    pub cell: CellMatcher,
+    pub neighbor: CellMatcher,
+    pub neighborhood: Neighborhood,
+    pub prob: f32,
+    pub catalyst: Option<CellMatcher>,
+    pub catalyst_multiplier: f32,
+    pub cell_product: Option<CellType>,
+    pub neighbor_product: Option<CellType>,
+    pub byproduct: Option<CellType>,
+}
+
+impl ReactionRule {
+    fn new(reaction: &Reaction, materials: &MaterialRegistry) -> Result<Self, String> {
+        let product = |name: &Option<String>| name.as_ref().map(|name| find_material(name, materials)).transpose();
+        let rule = ReactionRule {
+            trigger: reaction.trigger,
+            cell: CellMatcher::new(&reaction.cell, materials)?,
+            neighbor: CellMatcher::new(&reaction.neighbor, materials)?,
+            neighborhood: reaction.neighborhood,
+            prob: reaction.prob,
+            catalyst: reaction.catalyst.as_ref().map(|catalyst| CellMatcher::new(catalyst, materials)).transpose()?,
+            catalyst_multiplier: reaction.catalyst_multiplier,
+            cell_product: product(&reaction.cell_product)?,
+            neighbor_product: product(&reaction.neighbor_product)?,
+            byproduct: product(&reaction.byproduct)?,
+        };
+        if rule.cell == CellMatcher::Wall {
+            return Err(String::from("only the neighbor of a reaction can be a wall"));
+        }
+        if rule.neighbor == CellMatcher::Wall && (rule.trigger == ReactionTrigger::Ignition || rule.neighbor_product.is_some()) {
+            return Err(String::from("a wall can't be ignited or turned into a product"));
+        }
+        Ok(rule)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct ReactionTable
+{
+    pub rules: Vec<ReactionRule>,
+    // contact rules that cells of each material can take part in, in the order of the rules
+    pub contact: Vec<Vec<usize>>,
+    pub ignition: Vec<usize>,
+}
+
+impl ReactionTable {
+    pub fn new(reactions: &[Reaction], materials: &MaterialRegistry) -> Result<Self, String> {
+        let rules = reactions.iter().map(|reaction| ReactionRule::new(reaction, materials)).collect::<Result<Vec<_>, _>>()?;
+        let contact = materials.types().map(|cell_type| {
+            (0..rules.len()).filter(|i| rules[*i].trigger == ReactionTrigger::Contact && rules[*i].cell.matches_type(materials, cell_type)).collect()
+        }).collect();
+        let ignition = (0..rules.len()).filter(|i| rules[*i].trigger == ReactionTrigger::Ignition).collect();
+        Ok(ReactionTable { rules, contact, ignition })
+    }
+
+    pub fn contact_rules(&self, cell_type: CellType) -> impl Iterator<Item = &ReactionRule> {
+        self.contact[cell_type.index()].iter().map(|i| &self.rules[*i])
+    }
+
+    pub fn has_contact_rules(&self, cell_type: CellType) -> bool {
+        !self.contact[cell_type.index()].is_empty()
+    }
+
+    // first ignition rule for the burning cell and the neighbor it ignites
+    pub fn find_ignition(&self, materials: &MaterialRegistry, cell: &Cell, neighbor: &Cell) -> Option<&ReactionRule> {
+        self.ignition.iter().map(|i| &self.rules[*i]).find(|rule| rule.cell.matches_cell(materials, cell) && rule.neighbor.matches_cell(materials, neighbor))
+    }
+}
@@ -0,0 +1,447 @@
+use std::mem::take;
+
+use bevy_math::prelude::*;
+
+use crate::utils::*;
+use crate::cell::*;
+use crate::cell_grid::*;
+use crate::particles::*;
+use crate::boundaries::*;
+
+// neighbors that connect static solids into one body
+const BODY_OFFSETS: [IVec2; 4] = [IVec2::new(1, 0), IVec2::new(0, 1), IVec2::new(-1, 0), IVec2::new(0, -1)];
+// updates without any motion after which a body becomes a part of the grid again
+const BODY_REST_UPDATES: u32 = 10;
+// angular speed a body tips over with, when its center of mass is not above its support
+const BODY_TIP_SPEED: f32 = 0.02;
+const BODY_MAX_ANGULAR_SPEED: f32 = 0.1;
+// fraction of the horizontal speed kept after a collision
+const BODY_FRICTION: f32 = 0.5;
+
+// buffers of the search for the unsupported groups, kept from one search to the next
+pub struct BodySearchState
+{
+    // the cells visited by the search of the same number
+    visited: Vector2D<u32>,
+    // the cells of the moving bodies during the search of the same number
+    in_body: Vector2D<u32>,
+    search: u32,
+    component: Vec<IVec2>,
+    stack: Vec<IVec2>,
+}
+
+impl Default for BodySearchState {
+    fn default() -> Self
+    {
+        BodySearchState {
+            visited: Vector2D::new(IVec2::ZERO, 0),
+            in_body: Vector2D::new(IVec2::ZERO, 0),
+            search: 0,
+            component: Vec::new(),
+            stack: Vec::new(),
+        }
+    }
+}
+
+impl BodySearchState {
+    // the marks of the last search are left behind, they don't match the number of the new one
+    fn start_search(&mut self, sizes: IVec2)
+    {
+        if self.visited.sizes != sizes {
+            self.visited = Vector2D::new(sizes, 0);
+            self.in_body = Vector2D::new(sizes, 0);
+        }
+        self.search = self.search.wrapping_add(1);
+        if self.search == 0 {
+            // the old marks might match again, so they are cleared
+            self.visited.data.fill(0);
+            self.in_body.data.fill(0);
+            self.search = 1;
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct BodyCell
+{
+    pub cell: Cell,
+    // from the center of mass in the body space
+    pub offset: Vec2,
+}
+
+// group of connected static solids that fell off their support, it moves as a whole
+// its cells stay in the grid, where they are lifted from and placed back at every update
+#[derive(Clone)]
+pub struct RigidBody
+{
+    pub cells: Vec<BodyCell>,
+    // center of mass
+    pub pos: Vec2,
+    // counter-clockwise in radians
+    pub angle: f32,
+    pub vel: Vec2,
+    pub angular_vel: f32,
+    // grid positions of the cells, None for the cells that overlap with another cell of the body
+    pub placed: Vec<Option<IVec2>>,
+    pub rest_updates: u32,
+}
+
+impl RigidBody {
+    pub fn new(cells: &Vector2D<Cell>, positions: &[IVec2]) -> Self
+    {
+        let pos = positions.iter().map(|pos| pos.as_vec2() + 0.5).sum::<Vec2>() / positions.len() as f32;
+        RigidBody {
+            cells: positions.iter().map(|cell_pos| BodyCell { cell: cells[*cell_pos], offset: cell_pos.as_vec2() + 0.5 - pos }).collect(),
+            pos,
+            angle: 0.0,
+            vel: Vec2::ZERO,
+            angular_vel: 0.0,
+            placed: positions.iter().map(|pos| Some(*pos)).collect(),
+            rest_updates: 0,
+        }
+    }
+
+    pub fn cell_pos(&self, pos: Vec2, angle: f32, cell: &BodyCell) -> IVec2 {
+        (pos + Vec2::from_angle(angle).rotate(cell.offset) - 0.5).round().as_ivec2()
+    }
+
+    // moves the center back to the center of mass after the body lost some of its cells
+    pub fn recenter(&mut self)
+    {
+        if self.cells.is_empty() {
+            return;
+        }
+        let center = self.cells.iter().map(|cell| cell.offset).sum::<Vec2>() / self.cells.len() as f32;
+        for cell in self.cells.iter_mut() {
+            cell.offset -= center;
+        }
+        self.pos += Vec2::from_angle(self.angle).rotate(center);
+    }
+}
+
+impl CellGrid
+{
+    pub fn update_rigid_bodies(&mut self)
+    {
+        if !self.settings.rigid_bodies {
+            return;
+        }
+        for mut body in take(&mut self.bodies) {
+            // the bodies reaching into the frozen chunks wait until those are active again
+            if !body.placed.iter().flatten().all(|pos| self.chunks.is_active_cell(*pos)) {
+                self.bodies.push(body);
+                continue;
+            }
+            self.lift_body(&mut body);
+            if body.cells.is_empty() {
+                continue;
+            }
+            self.move_body(&mut body);
+            self.place_body(&mut body);
+            // a body at rest is a part of the grid again until it loses its support
+            if body.rest_updates < BODY_REST_UPDATES {
+                self.bodies.push(body);
+                continue;
+            }
+            // the cells that didn't fit into the grid fall off
+            for (cell, placed) in body.cells.iter().zip(body.placed.iter()) {
+                if placed.is_none() {
+                    let pos = body.pos + Vec2::from_angle(body.angle).rotate(cell.offset);
+                    self.particles.push(Particle { cell: cell.cell, pos, vel: Vec2::ZERO });
+                }
+            }
+        }
+        // no group can lose its support while no static solid and nothing below one changed
+        if self.updates.is_multiple_of(self.settings.body_check_interval.max(1) as u64) && self.chunks.take_supports_changed() {
+            let mut state = take(&mut self.body_search_state);
+            self.find_rigid_bodies(&mut state);
+            self.body_search_state = state;
+        }
+    }
+
+    // groups of static solids not supported from below or by the grid edges become bodies
+    // only the active chunks are searched, the groups reaching into the frozen ones are left alone
+    fn find_rigid_bodies(&mut self, state: &mut BodySearchState)
+    {
+        let sizes = self.cells.sizes;
+        let (min, max) = self.chunks.active_cells_rect();
+        state.start_search(sizes);
+        let search = state.search;
+        let BodySearchState { visited, in_body, component, stack, .. } = state;
+        for body in self.bodies.iter() {
+            for pos in body.placed.iter().flatten() {
+                visited[*pos] = search;
+                in_body[*pos] = search;
+            }
+        }
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                let start = IVec2::new(x, y);
+                if visited[start] == search || !self.is_static_solid(start) {
+                    continue;
+                }
+                visited[start] = search;
+                stack.push(start);
+                component.clear();
+                let mut reaches_frozen = false;
+                while let Some(pos) = stack.pop() {
+                    component.push(pos);
+                    for off in BODY_OFFSETS {
+                        let neighbor_pos = pos + off;
+                        if !self.cells.is_in_range(neighbor_pos) || visited[neighbor_pos] == search || !self.is_static_solid(neighbor_pos) {
+                            continue;
+                        }
+                        if neighbor_pos.cmplt(min).any() || neighbor_pos.cmpgt(max).any() {
+                            reaches_frozen = true;
+                            continue;
+                        }
+                        visited[neighbor_pos] = search;
+                        stack.push(neighbor_pos);
+                    }
+                }
+                if reaches_frozen {
+                    continue;
+                }
+                component.sort_by_key(|pos| (pos.y, pos.x));
+                // under a diagonal gravity the solid below isn't connected to the group, it holds the group like the floor
+                let rests_on_solid = |pos: &IVec2| self.settings.gravity_dirs_at(*pos).is_some_and(|dirs| {
+                    match self.settings.boundaries.move_target(*pos + dirs.down, sizes) {
+                        MoveTarget::Cell(below_pos) => in_body[below_pos] != search && self.is_static_solid(below_pos)
+                            && component.binary_search_by_key(&(below_pos.y, below_pos.x), |pos| (pos.y, pos.x)).is_err(),
+                        _ => false,
+                    }
+                });
+                if !component.iter().any(|pos| self.is_supported(*pos) || rests_on_solid(pos)) {
+                    self.bodies.push(RigidBody::new(&self.cells, component));
+                }
+            }
+        }
+    }
+
+    fn is_static_solid(&self, pos: IVec2) -> bool {
+        self.materials.phase(self.cells[pos].cell_type) == CellPhase::StaticSolid
+    }
+
+    // the cell rests on the floor, is attached to a side wall or lies on a powder or a liquid it can't sink into
+    // static solids below are either of the same group or of a moving body
+    // only the edges nothing can pass through hold the cells, the void and wrapping ones don't
+    // below and the sides follow the local gravity, without any gravity nothing falls
+    pub(crate) fn is_supported(&self, pos: IVec2) -> bool
+    {
+        let Some(dirs) = self.settings.gravity_dirs_at(pos) else {
+            return true;
+        };
+        let boundaries = &self.settings.boundaries;
+        let sizes = self.cells.sizes;
+        if dirs.sides.iter().any(|off| boundaries.move_target(pos + *off, sizes) == MoveTarget::Blocked) {
+            return true;
+        }
+        let below_pos = match boundaries.move_target(pos + dirs.down, sizes) {
+            MoveTarget::Cell(below_pos) => below_pos,
+            MoveTarget::Void => return false,
+            MoveTarget::Blocked => return true,
+        };
+        let below_type = self.cells[below_pos].cell_type;
+        if self.materials.is_liquid(below_type) {
+            return self.materials[below_type].density > self.materials[self.cells[pos].cell_type].density;
+        }
+        self.materials.is_powder(below_type)
+    }
+
+    // takes the cells out of the grid, the ones that were burned, melted or destroyed there are lost
+    fn lift_body(&mut self, body: &mut RigidBody)
+    {
+        let mut kept = Vec::with_capacity(body.cells.len());
+        for (body_cell, placed) in body.cells.iter().zip(body.placed.iter()) {
+            match placed {
+                Some(pos) => {
+                    let cell = self.cells[*pos];
+                    if cell.cell_type == body_cell.cell.cell_type {
+                        kept.push(BodyCell { cell, offset: body_cell.offset });
+                        self.replace_cell(*pos, CellType::Air);
+                    }
+                },
+                None => kept.push(*body_cell),
+            }
+        }
+        body.cells = kept;
+        body.placed.clear();
+        body.recenter();
+    }
+
+    // the cell of the body can't move into the position
+    fn is_blocked(&self, pos: IVec2, cell_type: CellType) -> bool
+    {
+        let pos = match self.settings.boundaries.move_target(pos, self.cells.sizes) {
+            MoveTarget::Cell(pos) => pos,
+            MoveTarget::Void => return false,
+            MoveTarget::Blocked => return true,
+        };
+        let other_type = self.cells[pos].cell_type;
+        if self.materials.is_liquid(other_type) {
+            return self.materials[other_type].density > self.materials[cell_type].density;
+        }
+        !self.materials.is_gass(other_type)
+    }
+
+    fn fits(&self, body: &RigidBody, pos: Vec2, angle: f32) -> bool {
+        body.cells.iter().all(|cell| !self.is_blocked(body.cell_pos(pos, angle, cell), cell.cell.cell_type))
+    }
+
+    fn move_body(&mut self, body: &mut RigidBody)
+    {
+        body.vel += self.settings.gravity_at(body.pos) * self.settings.particle_gravity;
+        body.vel = body.vel.clamp_length_max(self.settings.max_particle_speed);
+        // moves in steps of at most one cell as far as it fits
+        let steps = body.vel.abs().max_element().ceil().max(1.0) as i32;
+        let mut fitted = 0;
+        for step in 1..=steps {
+            let t = step as f32 / steps as f32;
+            if !self.fits(body, body.pos + body.vel * t, body.angle + body.angular_vel * t) {
+                break;
+            }
+            fitted = step;
+        }
+        let t = fitted as f32 / steps as f32;
+        body.pos += body.vel * t;
+        body.angle += body.angular_vel * t;
+        if fitted == steps {
+            // only a body floating without any gravity stays still while it fits
+            if body.vel == Vec2::ZERO && body.angular_vel == 0.0 {
+                body.rest_updates += 1;
+            } else {
+                body.rest_updates = 0;
+            }
+            return;
+        }
+        let speed = body.vel.length();
+        let mut moved = fitted > 0;
+        // the support is below along the local gravity, the body tips and slides along the side axis
+        let down = self.settings.gravity_at(body.pos).normalize_or_zero();
+        let side = down.perp();
+
+        let contacts = self.contacts(body, down);
+        self.crumble_body(body, &contacts, speed, down);
+        if body.cells.is_empty() {
+            return;
+        }
+        // the crumbled body rests on what is left of it
+        let rotation = Vec2::from_angle(body.angle);
+        let contact_xs: Vec<f32> = self.contacts(body, down).iter().map(|i| rotation.rotate(body.cells[*i].offset).dot(side)).collect();
+
+        // tips over the edge of the support, when the center of mass isn't above it
+        let min_x = contact_xs.iter().copied().fold(f32::INFINITY, f32::min);
+        let max_x = contact_xs.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let pivot = if contact_xs.is_empty() || (min_x <= 0.0 && max_x >= 0.0) {
+            body.angular_vel = 0.0;
+            None
+        } else if max_x < 0.0 {
+            body.angular_vel = (body.angular_vel.min(0.0) - BODY_TIP_SPEED).max(-BODY_MAX_ANGULAR_SPEED);
+            Some(side * max_x + down * 0.5)
+        } else {
+            body.angular_vel = (body.angular_vel.max(0.0) + BODY_TIP_SPEED).min(BODY_MAX_ANGULAR_SPEED);
+            Some(side * min_x + down * 0.5)
+        };
+        body.vel = side * body.vel.dot(side) * BODY_FRICTION;
+
+        if let Some(pivot) = pivot {
+            // rotates around the edge it rests on
+            let pivot = body.pos + pivot;
+            let new_pos = pivot + Vec2::from_angle(body.angular_vel).rotate(body.pos - pivot);
+            let new_angle = body.angle + body.angular_vel;
+            for lift in [0.0, 0.5] {
+                if self.fits(body, new_pos - down * lift, new_angle) {
+                    body.pos = new_pos - down * lift;
+                    body.angle = new_angle;
+                    moved = true;
+                    break;
+                }
+            }
+            if !moved {
+                body.angular_vel = 0.0;
+            }
+        }
+        if !moved && body.vel.length() > 0.1 && self.fits(body, body.pos + body.vel, body.angle) {
+            body.pos += body.vel;
+            moved = true;
+        }
+        if moved {
+            body.rest_updates = 0;
+        } else {
+            body.vel = Vec2::ZERO;
+            body.rest_updates += 1;
+        }
+    }
+
+    // cells resting on something below them, nothing is below without any gravity
+    fn contacts(&self, body: &RigidBody, down: Vec2) -> Vec<usize>
+    {
+        if down == Vec2::ZERO {
+            return Vec::new();
+        }
+        (0..body.cells.len()).filter(|i| {
+            let cell = &body.cells[*i];
+            self.is_blocked(body.cell_pos(body.pos + down, body.angle, cell), cell.cell.cell_type)
+        }).collect()
+    }
+
+    // cells weaker than the impact break off the body and fly away as particles
+    fn crumble_body(&mut self, body: &mut RigidBody, contacts: &[usize], speed: f32, down: Vec2)
+    {
+        let force = speed * self.settings.body_impact_strength;
+        let mut broken = Vec::new();
+        for i in contacts.iter().rev() {
+            let cell = body.cells[*i];
+            if force > self.materials[cell.cell.cell_type].strength {
+                broken.push(body.cells.remove(*i));
+            }
+        }
+        let side = down.perp();
+        for cell in broken.iter() {
+            let offset = Vec2::from_angle(body.angle).rotate(cell.offset);
+            let pos = body.pos + offset;
+            let vel = side * (body.vel.dot(side) + offset.dot(side).signum() * 0.2 * speed) - down * 0.3 * speed;
+            self.particles.push(Particle { cell: cell.cell, pos, vel });
+        }
+        if !broken.is_empty() {
+            body.recenter();
+        }
+    }
+
+    // liquids in the way are splashed out, the cells leaving the grid through a void edge are lost
+    fn place_body(&mut self, body: &mut RigidBody)
+    {
+        let up = -self.settings.gravity_at(body.pos).normalize_or(Vec2::NEG_Y);
+        let splash_vel = up * body.vel.length().max(1.0);
+        let cell_count = body.cells.len();
+        let mut kept = Vec::with_capacity(cell_count);
+        body.placed = Vec::with_capacity(cell_count);
+        for body_cell in take(&mut body.cells) {
+            let pos = match self.settings.boundaries.move_target(body.cell_pos(body.pos, body.angle, &body_cell), self.cells.sizes) {
+                MoveTarget::Cell(pos) => pos,
+                MoveTarget::Void => continue,
+                MoveTarget::Blocked => {
+                    kept.push(body_cell);
+                    body.placed.push(None);
+                    continue;
+                },
+            };
+            kept.push(body_cell);
+            if self.materials.phase(self.cells[pos].cell_type) == CellPhase::StaticSolid || self.materials.is_powder(self.cells[pos].cell_type) {
+                body.placed.push(None);
+                continue;
+            }
+            if self.materials.is_liquid(self.cells[pos].cell_type) {
+                self.launch_cell(pos, splash_vel);
+            }
+            self.cells[pos] = body_cell.cell;
+            self.chunks.wake_cell(pos);
+            self.chunks.mark_solids_changed();
+            body.placed.push(Some(pos));
+        }
+        body.cells = kept;
+        if body.cells.len() < cell_count {
+            body.recenter();
+        }
+    }
+}
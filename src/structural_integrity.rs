@@ -0,0 +1,154 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use bevy_math::prelude::*;
+
+use crate::utils::*;
+use crate::cell::*;
+use crate::cell_grid::*;
+
+// distances are kept in fixed point, so they can be ordered in the heap
+const DIST_SCALE: f32 = 16.0;
+// marks of the cells whose load isn't counted yet or is being counted
+const LOAD_UNKNOWN: u32 = u32::MAX;
+const LOAD_COUNTING: u32 = u32::MAX - 1;
+
+const ORTHOGONAL: [IVec2; 4] = [IVec2::new(1, 0), IVec2::new(0, 1), IVec2::new(-1, 0), IVec2::new(0, -1)];
+
+impl CellGrid
+{
+    // static solids reaching too far from their anchoring crack or fall
+    // the reach is measured along the solids, going up costs nothing, every other step costs
+    // more when the cell carries powders and liquids on top of it
+    // up and on top follow the local gravity
+    // only the active chunks are checked, the solids reaching into the frozen ones are anchored there
+    pub fn update_structural_integrity(&mut self)
+    {
+        if !self.settings.structural_integrity || !self.updates.is_multiple_of(self.settings.integrity_check_interval.max(1) as u64) {
+            return;
+        }
+        // nothing can crack while no static solid and no load on one changed
+        if !self.chunks.take_solids_changed() {
+            return;
+        }
+        let sizes = self.cells.sizes;
+        let (min, max) = self.chunks.active_cells_rect();
+        let is_active = |pos: IVec2| pos.cmpge(min).all() && pos.cmple(max).all();
+        let active_cells = || (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)));
+        let mut in_body = Vector2D::<bool>::new(sizes, false);
+        for body in self.bodies.iter() {
+            for pos in body.placed.iter().flatten() {
+                in_body[*pos] = true;
+            }
+        }
+        let is_part = |grid: &CellGrid, pos: IVec2| {
+            is_active(pos) && !in_body[pos] && grid.materials.phase(grid.cells[pos].cell_type) == CellPhase::StaticSolid
+        };
+        let is_frozen_solid = |grid: &CellGrid, pos: IVec2| {
+            grid.cells.is_in_range(pos) && !is_active(pos) && grid.materials.phase(grid.cells[pos].cell_type) == CellPhase::StaticSolid
+        };
+
+        // powders and liquids lying on each cell, counted up through the static solids until a gap
+        // the stacks are shared by the cells below them, so each cell is counted once
+        let mut load = Vector2D::<u32>::new(sizes, 0);
+        let mut stacked = Vector2D::<u32>::new(sizes, LOAD_UNKNOWN);
+        let mut chain = Vec::new();
+        for pos in active_cells() {
+            if !is_part(self, pos) {
+                continue;
+            }
+            let Some(dirs) = self.settings.gravity_dirs_at(pos) else {
+                continue;
+            };
+            // walks up until a stack that is already counted, then counts the walked cells back down
+            chain.clear();
+            let mut top = pos - dirs.down;
+            let mut top_load = loop {
+                if !self.cells.is_in_range(top) || stacked[top] == LOAD_COUNTING {
+                    // around a gravity point the way up can lead back to itself
+                    break 0;
+                }
+                if stacked[top] != LOAD_UNKNOWN {
+                    break stacked[top];
+                }
+                let cell_type = self.cells[top].cell_type;
+                if !self.materials.is_powder(cell_type) && !self.materials.is_liquid(cell_type) && !is_part(self, top) {
+                    stacked[top] = 0;
+                    break 0;
+                }
+                stacked[top] = LOAD_COUNTING;
+                chain.push(top);
+                match self.settings.gravity_dirs_at(top) {
+                    Some(top_dirs) => top -= top_dirs.down,
+                    None => break 0,
+                }
+            };
+            for chain_pos in chain.iter().rev() {
+                let cell_type = self.cells[*chain_pos].cell_type;
+                if self.materials.is_powder(cell_type) || self.materials.is_liquid(cell_type) {
+                    top_load += 1;
+                }
+                stacked[*chain_pos] = top_load;
+            }
+            load[pos] = top_load;
+        }
+
+        let mut dist = Vector2D::<u32>::new(sizes, u32::MAX);
+        let mut heap = BinaryHeap::new();
+        for pos in active_cells() {
+            if !is_part(self, pos) {
+                continue;
+            }
+            if self.is_supported(pos) || ORTHOGONAL.iter().any(|off| is_frozen_solid(self, pos + *off)) {
+                dist[pos] = 0;
+                heap.push(Reverse((0u32, self.cells.vec_to_index(pos))));
+            }
+        }
+        while let Some(Reverse((pos_dist, i))) = heap.pop() {
+            let pos = self.cells.index_to_vec(i);
+            if pos_dist > dist[pos] {
+                continue;
+            }
+            let up = -self.settings.gravity_at(pos.as_vec2() + 0.5).normalize_or_zero();
+            for off in ORTHOGONAL {
+                let neighbor_pos = pos + off;
+                if !self.cells.is_in_range(neighbor_pos) || !is_part(self, neighbor_pos) {
+                    continue;
+                }
+                let step = if off.as_vec2().dot(up) > 0.5 { 0.0 } else { 1.0 + self.settings.integrity_load * load[neighbor_pos] as f32 };
+                let neighbor_dist = pos_dist.saturating_add((step * DIST_SCALE) as u32);
+                if neighbor_dist < dist[neighbor_pos] {
+                    dist[neighbor_pos] = neighbor_dist;
+                    heap.push(Reverse((neighbor_dist, self.cells.vec_to_index(neighbor_pos))));
+                }
+            }
+        }
+
+        let mut failed = Vec::new();
+        for pos in active_cells() {
+            if !is_part(self, pos) {
+                continue;
+            }
+            // groups without any anchoring fall as a whole when there are rigid bodies
+            if dist[pos] == u32::MAX && self.settings.rigid_bodies {
+                continue;
+            }
+            let reach = self.materials[self.cells[pos].cell_type].strength * self.settings.integrity_reach_per_strength;
+            if dist[pos] as f32 > reach * DIST_SCALE {
+                failed.push(pos);
+            }
+        }
+        for pos in failed {
+            match self.materials.get(self.cells[pos].cell_type).cracked_type {
+                Some(cracked_type) => {
+                    let temperature = self.cells[pos].temperature;
+                    self.replace_cell(pos, cracked_type);
+                    self.cells[pos].temperature = temperature;
+                },
+                None => {
+                    self.launch_cell(pos, Vec2::ZERO);
+                },
+            }
+        }
+    }
+}
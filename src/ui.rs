@@ -1,12 +1,15 @@
 use bevy::color::palettes::css::GOLD;
 use bevy::render::globals;
 use bevy::ui::widget::NodeImageMode;
+use bevy::image::{ImageSampler, ImageSamplerDescriptor};
+use bevy::render::{render_asset::RenderAssetUsages, render_resource::{Extent3d, TextureDimension, TextureFormat}};
 use bevy::{prelude::*, ui::RelativeCursorPosition};
-use enum_map::{Enum, EnumMap};
 
-use crate::color_settings::color_palette_button_image;
+use crate::color_settings::ColorSettings;
+use crate::cell_grid::BrushType;
+use crate::img_utils::*;
 use crate::utils::*;
-use crate::{cell::*, GameGlobals};
+use crate::{boundaries::Edge, cell::*, grid_chunks::CHUNK_SIZE, materials::*, GameGlobals};
 use crate::brush_icons::*;
 
 #[derive(Component)]
@@ -38,7 +41,49 @@ pub struct LoadButton;
 pub struct ReplaceSolidsButton;
 
 #[derive(Component)]
-pub struct TopGassLeakButton;
+pub struct BoundaryButton
+{
+    pub edge: Edge,
+    pub pressed: bool,
+}
+
+// switches turned on and off by the toggle buttons
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ToggleSetting {
+    RigidBodies,
+    StructuralIntegrity,
+    LiquidPressure,
+    HeatMap,
+}
+
+#[derive(Component)]
+pub struct ToggleButton
+{
+    pub setting: ToggleSetting,
+    pub pressed: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GridDimension {
+    Width,
+    Height,
+    Scale,
+}
+
+#[derive(Component)]
+pub struct GridSizeButton
+{
+    pub dimension: GridDimension,
+    // -1 shrinks, 1 grows
+    pub step: i32,
+    pub pressed: bool,
+}
+
+#[derive(Component)]
+pub struct GridSizeText
+{
+    pub dimension: GridDimension,
+}
 
 #[derive(Component)]
 pub struct ColorPalleteButton
@@ -71,15 +116,6 @@ pub struct CellTypeButtonConfig
     pub name: String,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Enum, Component)]
-pub enum BrushType
-{
-    Circle,
-    Square,
-    LineRound,
-    LineSharp,
-}
-
 pub const TEXT_LIGHT: Color = Color::rgb(0.88235, 0.88235, 0.88235);
 pub const TEXT_DIMM: Color = Color::rgb(0.77843, 0.77843, 0.77843);
 
@@ -126,76 +162,25 @@ pub const SLIDER_SIZE_MUL: i32 = 4;
 
 pub const SLIDER_BUTTON_COLOR: Color = Color::rgb(0.35, 0.35, 0.35);
 
-pub fn get_cell_type_buttons_config() -> Vec<CellTypeButtonConfig>
+// grid sizes in whole pairs of chunks keep the parallel update with wrapping edges
+pub const GRID_SIZE_STEP: i32 = 2 * CHUNK_SIZE;
+pub const MIN_GRID_SIZE: i32 = GRID_SIZE_STEP;
+// a cell takes 20 bytes and the liquid pressure, rigid body and structural integrity passes keep up to 29 bytes more per cell,
+// so the largest grid needs about 200 MB, which the frozen chunks don't lessen
+pub const MAX_GRID_SIZE: i32 = 2048;
+pub const MAX_PIXEL_SCALE: u32 = 8;
+
+pub fn get_cell_type_buttons_config(materials: &MaterialRegistry) -> Vec<CellTypeButtonConfig>
 {
-    vec![
-        //CellTypeButtonConfig {
-        //    cell_type: CellType::Air,
-        //    name: String::from("Air"),
-        //},
-        CellTypeButtonConfig {
-            cell_type: CellType::Sand,
-            name: String::from("Sand"),
-        },
-        CellTypeButtonConfig {
-            cell_type: CellType::Water,
-            name: String::from("Water"),
-        },
-        CellTypeButtonConfig {
-            cell_type: CellType::Wood,
-            name: String::from("Wood"),
-        },
-        CellTypeButtonConfig {
-            cell_type: CellType::Stone,
-            name: String::from("Stone"),
-        },
-        CellTypeButtonConfig {
-            cell_type: CellType::Coal,
-            name: String::from("Coal"),
-        },
-        CellTypeButtonConfig {
-            cell_type: CellType::Fire,
-            name: String::from("Fire"),
-        },
-        CellTypeButtonConfig {
-            cell_type: CellType::FlammableGass,
-            name: String::from("F. Gass"),
-        },
-        CellTypeButtonConfig {
-            cell_type: CellType::Oil,
-            name: String::from("Oil"),
-        },
-        CellTypeButtonConfig {
-            cell_type: CellType::Glass,
-            name: String::from("Glass"),
-        },
-        CellTypeButtonConfig {
-            cell_type: CellType::Acid,
-            name: String::from("Acid"),
-        },
-        CellTypeButtonConfig {
-            cell_type: CellType::Lava,
-            name: String::from("Lava"),
-        },
-        CellTypeButtonConfig {
-            cell_type: CellType::Ice,
-            name: String::from("Ice"),
-        },
-        //CellTypeButtonConfig {
-        //    cell_type: CellType::Steam,
-        //    name: String::from("Steam"),
-        //},
-        //CellTypeButtonConfig {
-        //    cell_type: CellType::Smoke,
-        //    name: String::from("Smoke"),
-        //},
-    ]
+    materials.buttons.iter().map(|cell_type| CellTypeButtonConfig {
+        cell_type: *cell_type,
+        name: materials.get(*cell_type).label.clone(),
+    }).collect()
 }
 
 pub fn setup_ui(
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
-    out_tex_size: u32,
     img_handle: Handle<Image>,
     images: &mut ResMut<Assets<Image>>,
     globals: &GameGlobals,
@@ -252,7 +237,6 @@ pub fn setup_ui(
                 ..default()
             }, BackgroundColor(MAIN_BACKGROUND_COLOR))).with_children(|parent| {
                 parent.spawn((Node {
-                    //width: Val::Px((out_tex_size + 20) as f32),
                     flex_direction: FlexDirection::Column,
                     justify_content: JustifyContent::Center,
                     align_items: AlignItems::Center,
@@ -261,8 +245,8 @@ pub fn setup_ui(
                 }, BackgroundColor(SECTION_BACKGROUND_COLOR))).with_children(|parent| {
                     parent.spawn((
                         Node{
-                            height: Val::Px(out_tex_size as f32),
-                            width: Val::Px(out_tex_size as f32),
+                            height: Val::Px(globals.canvas_size().y),
+                            width: Val::Px(globals.canvas_size().x),
                             ..default()
                         },
                         BackgroundColor(Color::WHITE),
@@ -298,6 +282,8 @@ pub fn setup_ui(
                     color_palette_selection(parent, asset_server, globals, images);
                     // Toggle settings
                     toggle_settings(parent, asset_server);
+                    // Grid size
+                    grid_size(parent, asset_server);
                     // Save & Load buttons
                     #[cfg(not(target_arch = "wasm32"))]
                     save_and_load_buttons(parent, asset_server);
@@ -472,6 +458,7 @@ fn brush_type(
             add_brush_type_button(parent, asset_server, images, BrushType::Square);
             add_brush_type_button(parent, asset_server, images, BrushType::LineRound);
             add_brush_type_button(parent, asset_server, images, BrushType::LineSharp);
+            add_brush_type_button(parent, asset_server, images, BrushType::Fling);
         });
     });
 }
@@ -605,7 +592,7 @@ fn material_buttons(
     globals: &GameGlobals,
 ) {
     let buttons_config = &globals.buttons_config;
-    let cell_properties = &globals.grid.cell_properties;
+    let materials = &globals.grid.materials;
     parent.spawn((Node {
         flex_direction: FlexDirection::Column,
         justify_content: JustifyContent::Center,
@@ -618,7 +605,7 @@ fn material_buttons(
         // Buttons grid
         parent.spawn((Node {
             display: Display::Grid,
-            grid_template_columns: RepeatedGridTrack::flex(2, 1.0),
+            grid_template_columns: RepeatedGridTrack::flex(3, 1.0),
             //grid_template_rows: RepeatedGridTrack::flex(10, 1.0),
             row_gap: Val::Px(10.),
             column_gap: Val::Px(10.0),
@@ -627,7 +614,7 @@ fn material_buttons(
         .with_children(|parent| {
             // add buttons
             for button_config in buttons_config {
-                add_cell_type_button(parent, &asset_server, cell_properties, button_config);
+                add_cell_type_button(parent, &asset_server, materials, button_config);
             }
         });
     });
@@ -683,11 +670,11 @@ fn add_color_palette_button(
 ) {
     let gap_size = 4;
     let color_size = 16;
-    let grid_size = UVec2::new(4, 3);
+    let cell_types = &globals.buttons_config;
+    let grid_size = UVec2::new(4, (cell_types.len() as u32).div_ceil(4));
     let palette = &globals.color_settings[palette_num];
-    let cell_properties = &globals.grid.cell_properties;
-    let cell_types = get_cell_type_buttons_config();
-    let img = color_palette_button_image(palette, &cell_types, cell_properties, grid_size, color_size, gap_size);
+    let materials = &globals.grid.materials;
+    let img = color_palette_button_image(palette, cell_types, materials, grid_size, color_size, gap_size);
     let button_size = UVec2::new(img.width(), img.height()).as_vec2();
     let img_handle = images.add(img);
 
@@ -724,7 +711,93 @@ fn toggle_settings(
     }, BackgroundColor(SUBSECTION_BACKGROUND_COLOR)))
     .with_children(|parent| {
         replace_solids_button(parent, asset_server);
-        top_gass_leak_button(parent, asset_server);
+        for edge in Edge::ALL {
+            boundary_button(parent, asset_server, edge);
+        }
+        toggle_button(parent, asset_server, ToggleSetting::RigidBodies, "Rigid bodies");
+        toggle_button(parent, asset_server, ToggleSetting::StructuralIntegrity, "Structural integrity");
+        toggle_button(parent, asset_server, ToggleSetting::LiquidPressure, "Liquid pressure");
+        toggle_button(parent, asset_server, ToggleSetting::HeatMap, "Heat map");
+    });
+}
+
+fn grid_size(
+    parent: &mut ChildBuilder,
+    asset_server: &Res<AssetServer>,
+) {
+    parent.spawn((Node {
+        flex_direction: FlexDirection::Column,
+        width: Val::Percent(100.0),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        padding: SUBSECTION_PADDING,
+        row_gap: SUBSECTION_ROW_GAP,
+        ..default()
+    }, BackgroundColor(SUBSECTION_BACKGROUND_COLOR)))
+    .with_children(|parent| {
+        for dimension in [GridDimension::Width, GridDimension::Height, GridDimension::Scale] {
+            parent.spawn((Node {
+                flex_direction: FlexDirection::Row,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                column_gap: Val::Px(10.),
+                ..default()
+            }, BackgroundColor(SUBSECTION_BACKGROUND_COLOR)))
+            .with_children(|parent| {
+                grid_size_button(parent, asset_server, dimension, -1, "-");
+                // the value is filled in from the grid
+                parent.spawn((
+                    Node {
+                        width: Val::Px(100.0),
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    },
+                    Text::new(""),
+                    TextFont {
+                        font: asset_server.load(TEXT_FONT),
+                        font_size: 16.0,
+                        ..default()
+                    },
+                    TextColor(TEXT_LIGHT),
+                    GridSizeText { dimension }
+                ));
+                grid_size_button(parent, asset_server, dimension, 1, "+");
+            });
+        }
+    });
+}
+
+fn grid_size_button(
+    parent: &mut ChildBuilder,
+    asset_server: &Res<AssetServer>,
+    dimension: GridDimension,
+    step: i32,
+    text: &str
+) {
+    parent.spawn((
+        Button,
+        Node {
+            width: Val::Px(40.0),
+            height: Val::Px(40.0),
+            border: BUTTON_BORDER,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        BorderColor(BASIC_BUTTON_BORDER_COLOR),
+        BackgroundColor(BASIC_BUTTON_BACKGROUND_COLOR),
+        GridSizeButton { dimension, step, pressed: false }
+    ))
+    .with_children(|parent| {
+        parent.spawn((
+            Text::new(text),
+            TextFont {
+                font: asset_server.load(TEXT_FONT),
+                font_size: 20.0,
+                ..default()
+            },
+            TextColor(BASIC_BUTTON_TEXT_COLOR)
+        ));
     });
 }
 
@@ -759,9 +832,10 @@ fn replace_solids_button(
     });
 }
 
-fn top_gass_leak_button(
+fn boundary_button(
     parent: &mut ChildBuilder,
     asset_server: &Res<AssetServer>,
+    edge: Edge,
 ) {
     parent.spawn((
         Button,
@@ -775,11 +849,45 @@ fn top_gass_leak_button(
         },
         BorderColor(BASIC_BUTTON_BORDER_COLOR),
         BackgroundColor(BASIC_BUTTON_BACKGROUND_COLOR),
-        TopGassLeakButton
+        BoundaryButton { edge, pressed: false }
     ))
     .with_children(|parent| {
+        // the label is filled in from the grid settings
         parent.spawn((
-            Text::new("Top gass leak"),
+            Text::new(""),
+            TextFont {
+                font: asset_server.load(TEXT_FONT),
+                font_size: 16.0,
+                ..default()
+            },
+            TextColor(BASIC_BUTTON_TEXT_COLOR)
+        ));
+    });
+}
+
+fn toggle_button(
+    parent: &mut ChildBuilder,
+    asset_server: &Res<AssetServer>,
+    setting: ToggleSetting,
+    text: &str
+) {
+    parent.spawn((
+        Button,
+        Node {
+            width: Val::Px(200.0),
+            height: Val::Px(50.0),
+            border: BUTTON_BORDER,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        BorderColor(BASIC_BUTTON_BORDER_COLOR),
+        BackgroundColor(BASIC_BUTTON_BACKGROUND_COLOR),
+        ToggleButton { setting, pressed: false }
+    ))
+    .with_children(|parent| {
+        parent.spawn((
+            Text::new(text),
             TextFont {
                 font: asset_server.load(TEXT_FONT),
                 font_size: 20.0,
@@ -939,24 +1047,42 @@ fn controls_help(
             },
             TextColor(TEXT_DIMM)
         ));
+        parent.spawn((
+            Text::new("Mouse Middle, Arrows - move view"),
+            TextFont {
+                font: asset_server.load(TEXT_FONT),
+                font_size: 20.0,
+                ..default()
+            },
+            TextColor(TEXT_DIMM)
+        ));
+        parent.spawn((
+            Text::new("Ctrl + Scroll, +/- - zoom"),
+            TextFont {
+                font: asset_server.load(TEXT_FONT),
+                font_size: 20.0,
+                ..default()
+            },
+            TextColor(TEXT_DIMM)
+        ));
     });
 }
 
 fn add_cell_type_button(
     parent: &mut ChildBuilder,
     asset_server: &Res<AssetServer>,
-    cell_properties: &EnumMap<CellType, CellTypeProperties>,
+    materials: &MaterialRegistry,
     button_config: &CellTypeButtonConfig
 ) {
-    let cell_color = cell_properties[button_config.cell_type].get_default_color();
-    let background_color = cell_properties[CellType::Air].get_default_color();
+    let cell_color = materials[button_config.cell_type].get_default_color();
+    let background_color = materials[CellType::Air].get_default_color();
     let cell_color = background_color.mix(&cell_color, cell_color.alpha());
     let text_color = distant_color_black_white_no_alpha(cell_color);
     parent.spawn((
         Button,
         Node {
-            width: Val::Px(150.0),
-            height: Val::Px(50.0),
+            width: Val::Px(100.0),
+            height: Val::Px(36.0),
             border: BUTTON_BORDER,
             // horizontally center child text
             justify_content: JustifyContent::Center,
@@ -973,10 +1099,58 @@ fn add_cell_type_button(
             Text::new(&button_config.name),
             TextFont {
                 font: asset_server.load(TEXT_FONT),
-                font_size: 30.0,
+                font_size: 20.0,
                 ..default()
             },
             TextColor(text_color)
         ));
     });
-}
\ No newline at end of file
+}
+
+pub fn color_palette_button_image(
+    palette: &ColorSettings,
+    cell_types: &Vec<CellTypeButtonConfig>,
+    materials: &MaterialRegistry,
+    grid_size: UVec2,
+    color_size: u32,
+    gap_size: u32
+) -> Image
+{
+    let img_size = (grid_size + 3) * gap_size + grid_size * color_size;
+    let mut img = Image::new(
+        Extent3d { width: img_size.x, height: img_size.y, depth_or_array_layers: 1 }, 
+        TextureDimension::D2,
+        vec![255u8; (img_size.x * img_size.y * 4) as usize],
+        TextureFormat::Rgba8Unorm,
+        RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD
+    );
+
+    img.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
+        min_filter: bevy::image::ImageFilterMode::Nearest,
+        ..default()
+    });
+    
+    let air_colors = palette.get(&CellType::Air).unwrap_or(&materials.get(CellType::Air).base_colors);
+    let background_color = materials[CellType::Air].get_default_color_custom(air_colors.clone());
+
+    fill_img_color(BASIC_BUTTON_BACKGROUND_COLOR, &mut img);
+
+    for y in 0..grid_size.y {
+        for x in 0..grid_size.x {
+            let grid_pos = UVec2::new(x, y);
+            let from = (grid_pos + 2) * gap_size + grid_pos * color_size;
+            let to = from + color_size;
+            let i = (x + y * grid_size.x) as usize;
+            if i >= cell_types.len() {
+                continue;
+            }
+            let cell_type = cell_types[i].cell_type;
+            let cell_colors = palette.get(&cell_type).unwrap_or(&materials.get(cell_type).base_colors).clone();
+            let color = materials[cell_type].get_default_color_custom(cell_colors);
+            let color = background_color.mix(&color, color.alpha());
+            fill_sub_img_color(color, &mut img, from, to);
+        }
+    }
+    
+    img
+}
@@ -1,7 +1,7 @@
 
 use bevy::{diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin}, input::mouse::MouseWheel, prelude::*, tasks::block_on, ui::RelativeCursorPosition};
 
-use crate::{cell::CellType, color_settings::ColorSettings, input::*, ui::*, utils::clamp, FpsDisplayTimer, GameGlobals};
+use crate::{boundaries::BoundaryType, cell::CellType, cell_grid::BrushType, color_settings::ColorSettings, input::*, ui::*, utils::clamp, FpsDisplayTimer, GameGlobals};
 
 use rfd::AsyncFileDialog;
 
@@ -19,18 +19,18 @@ pub fn cell_type_button_interactions(
 ) {
     let mut globals = globals_query.single_mut();
     for (interaction, mut color, mut border_color, cell_type) in &mut interaction_query {
-        let background_color = globals.grid.cell_properties[CellType::Air].get_default_color();
-        let color_radius = globals.grid.cell_properties[*cell_type].color_rand_radius;
+        let background_color = globals.grid.materials[CellType::Air].get_default_color();
+        let color_radius = globals.grid.materials[*cell_type].color_rand_radius;
         match *interaction {
             Interaction::Pressed => {
-                let cell_color = globals.grid.cell_properties[*cell_type].get_default_color_scaled(1.0 + color_radius);
+                let cell_color = globals.grid.materials[*cell_type].get_default_color_scaled(1.0 + color_radius);
                 let cell_color = background_color.mix(&cell_color, cell_color.alpha());
                 *color = cell_color.into();
                 border_color.0 = CELL_BUTTON_SELECTED_BORDER_COLOR;
                 globals.place_cell_type = *cell_type;
             }
             Interaction::Hovered => {
-                let cell_color = globals.grid.cell_properties[*cell_type].get_default_color_scaled(1.0 + 0.5 * color_radius);
+                let cell_color = globals.grid.materials[*cell_type].get_default_color_scaled(1.0 + 0.5 * color_radius);
                 let cell_color = background_color.mix(&cell_color, cell_color.alpha());
                 *color = cell_color.into();
                 if globals.place_cell_type == *cell_type {
@@ -40,7 +40,7 @@ pub fn cell_type_button_interactions(
                 }
             }
             Interaction::None => {
-                let cell_color = globals.grid.cell_properties[*cell_type].get_default_color();
+                let cell_color = globals.grid.materials[*cell_type].get_default_color();
                 let cell_color = background_color.mix(&cell_color, cell_color.alpha());
                 *color = cell_color.into();
                 if globals.place_cell_type == *cell_type {
@@ -180,10 +180,7 @@ pub fn set_color_palette(
     palette_num: usize,
 ) {
     let palette = &globals.color_settings[palette_num];
-    for (cell_type, colors) in palette
-    {
-        globals.grid.cell_properties[cell_type].colors = colors.clone();
-    }
+    globals.grid.materials.set_palette(palette);
 }
 
 pub fn color_pallete_button_interactions(
@@ -356,28 +353,158 @@ pub fn replace_solids_button_interactions(
     }
 }
 
-pub fn top_gass_leak_button_interactions(
+pub fn boundary_button_interactions(
     mut globals_query: Query<&mut GameGlobals>,
     mut interaction_query: Query<
         (
             &Interaction,
             &mut BackgroundColor,
             &mut BorderColor,
-            &TopGassLeakButton
+            &Children,
+            &mut BoundaryButton
+        ),
+        With<Button>,
+    >,
+    mut text_query: Query<&mut Text>,
+) {
+    let mut globals = globals_query.single_mut();
+    for (interaction, mut color, mut border_color, children, mut button) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                *color = BASIC_BUTTON_HOVER_BACKGROUND_COLOR.into();
+                border_color.0 = BASIC_BUTTON_SELECTED_BORDER_COLOR;
+                // the inflow spawns the selected material
+                if !button.pressed {
+                    button.pressed = true;
+                    let boundary_type = globals.grid.settings.boundaries.boundary_type(button.edge).next();
+                    let inflow_type = globals.place_cell_type;
+                    globals.grid.set_boundary(button.edge, boundary_type, inflow_type);
+                }
+            }
+            Interaction::Hovered => {
+                button.pressed = false;
+                *color = BASIC_BUTTON_HOVER_BACKGROUND_COLOR.into();
+                border_color.0 = BASIC_BUTTON_HOVER_BORDER_COLOR;
+            }
+            Interaction::None => {
+                button.pressed = false;
+                *color = BASIC_BUTTON_BACKGROUND_COLOR.into();
+                border_color.0 = BASIC_BUTTON_BORDER_COLOR;
+            }
+        }
+        let boundary = globals.grid.settings.boundaries.get(button.edge);
+        let label = match boundary.boundary_type {
+            BoundaryType::Inflow => format!("{:?}: {} inflow", button.edge, globals.grid.materials.get(boundary.inflow_type).label),
+            boundary_type => format!("{:?}: {:?}", button.edge, boundary_type),
+        };
+        let mut text = text_query.get_mut(children[0]).unwrap();
+        if text.0 != label {
+            text.0 = label;
+        }
+    }
+}
+
+// snaps the size to the next multiple of the grid size step in the direction of the step
+fn step_grid_size(size: i32, step: i32) -> i32
+{
+    let size = if step > 0 {
+        (size / GRID_SIZE_STEP + 1) * GRID_SIZE_STEP
+    } else {
+        (size - 1) / GRID_SIZE_STEP * GRID_SIZE_STEP
+    };
+    size.clamp(MIN_GRID_SIZE, MAX_GRID_SIZE)
+}
+
+pub fn grid_size_button_interactions(
+    mut globals_query: Query<&mut GameGlobals>,
+    mut interaction_query: Query<
+        (
+            &Interaction,
+            &mut BackgroundColor,
+            &mut BorderColor,
+            &mut GridSizeButton
+        ),
+        With<Button>,
+    >,
+    mut text_query: Query<(&mut Text, &GridSizeText)>,
+) {
+    let mut globals = globals_query.single_mut();
+    for (interaction, mut color, mut border_color, mut button) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                *color = BASIC_BUTTON_HOVER_BACKGROUND_COLOR.into();
+                border_color.0 = BASIC_BUTTON_SELECTED_BORDER_COLOR;
+                if !button.pressed {
+                    button.pressed = true;
+                    let mut grid_sizes = globals.grid.cells.sizes;
+                    match button.dimension {
+                        GridDimension::Width => grid_sizes.x = step_grid_size(grid_sizes.x, button.step),
+                        GridDimension::Height => grid_sizes.y = step_grid_size(grid_sizes.y, button.step),
+                        GridDimension::Scale => globals.zoom_view(button.step),
+                    }
+                    globals.grid.resize(grid_sizes);
+                    globals.pan_view(Vec2::ZERO);
+                }
+            }
+            Interaction::Hovered => {
+                button.pressed = false;
+                *color = BASIC_BUTTON_HOVER_BACKGROUND_COLOR.into();
+                border_color.0 = BASIC_BUTTON_HOVER_BORDER_COLOR;
+            }
+            Interaction::None => {
+                button.pressed = false;
+                *color = BASIC_BUTTON_BACKGROUND_COLOR.into();
+                border_color.0 = BASIC_BUTTON_BORDER_COLOR;
+            }
+        }
+    }
+
+    let grid_sizes = globals.grid.cells.sizes;
+    for (mut text, size_text) in &mut text_query {
+        let label = match size_text.dimension {
+            GridDimension::Width => format!("Width {}", grid_sizes.x),
+            GridDimension::Height => format!("Height {}", grid_sizes.y),
+            GridDimension::Scale => format!("Scale {}x", globals.pixel_scale),
+        };
+        if text.0 != label {
+            text.0 = label;
+        }
+    }
+}
+
+fn toggle_value(globals: &mut GameGlobals, setting: ToggleSetting) -> &mut bool
+{
+    match setting {
+        ToggleSetting::RigidBodies => &mut globals.grid.settings.rigid_bodies,
+        ToggleSetting::StructuralIntegrity => &mut globals.grid.settings.structural_integrity,
+        ToggleSetting::LiquidPressure => &mut globals.grid.settings.liquid_pressure,
+        ToggleSetting::HeatMap => &mut globals.heat_map,
+    }
+}
+
+pub fn toggle_button_interactions(
+    mut globals_query: Query<&mut GameGlobals>,
+    mut interaction_query: Query<
+        (
+            &Interaction,
+            &mut BackgroundColor,
+            &mut BorderColor,
+            &mut ToggleButton
         ),
         With<Button>,
     >
 ) {
     let mut globals = globals_query.single_mut();
-    for (interaction, mut color, mut border_color, _) in &mut interaction_query {
+    for (interaction, mut color, mut border_color, mut button) in &mut interaction_query {
+        let value = toggle_value(&mut globals, button.setting);
         match *interaction {
             Interaction::Pressed => {
                 *color = BASIC_BUTTON_HOVER_BACKGROUND_COLOR.into();
-                if !globals.top_gass_leak_button_pressed {
-                    globals.grid.top_gass_leak = !globals.grid.top_gass_leak;
-                    globals.top_gass_leak_button_pressed = true;
+                if !button.pressed {
+                    *value = !*value;
+                    button.pressed = true;
                 }
-                if globals.grid.top_gass_leak {
+                if *value {
                     border_color.0 = BASIC_BUTTON_SELECTED_BORDER_COLOR;
                 } else {
                     border_color.0 = BASIC_BUTTON_HOVER_BORDER_COLOR;
@@ -385,21 +512,21 @@ pub fn top_gass_leak_button_interactions(
             }
             Interaction::Hovered => {
                 *color = BASIC_BUTTON_HOVER_BACKGROUND_COLOR.into();
-                if globals.grid.top_gass_leak {
+                if *value {
                     border_color.0 = BASIC_BUTTON_SELECTED_BORDER_COLOR;
                 } else {
                     border_color.0 = BASIC_BUTTON_HOVER_BORDER_COLOR;
                 }
-                globals.top_gass_leak_button_pressed = false;
+                button.pressed = false;
             }
             Interaction::None => {
                 *color = BASIC_BUTTON_BACKGROUND_COLOR.into();
-                if globals.grid.top_gass_leak {
+                if *value {
                     border_color.0 = BASIC_BUTTON_SELECTED_BORDER_COLOR;
                 } else {
                     border_color.0 = BASIC_BUTTON_BORDER_COLOR;
                 }
-                globals.top_gass_leak_button_pressed = false;
+                button.pressed = false;
             }
         }
     }
@@ -440,6 +567,7 @@ pub fn update_brush_size_slider_value(
 pub fn brush_size_mouse_scroll(
     mut globals_query: Query<&mut GameGlobals>,
     mut mouse_wheel_events: EventReader<MouseWheel>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
     mut brush_size_text_query: Query<&mut Text, With<BrushSizeText>>,
     mut slider_style_query: Query<&mut Node, With<BrushSizeSliderButton>>
 )
@@ -449,6 +577,11 @@ pub fn brush_size_mouse_scroll(
 
     for event in mouse_wheel_events.read() {
         let dir = clamp(event.y as i32, -3, 3);
+        // zooms with control held instead
+        if keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+            globals.zoom_view(dir.signum());
+            continue;
+        }
         globals.brush_size += dir;
         if globals.brush_size < 0 {
             globals.brush_size = 0;
@@ -1,4 +1,5 @@
-use bevy::{color::palettes::css::{BLACK, WHITE}, prelude::*};
+use bevy_math::prelude::*;
+use bevy_color::{palettes::css::{BLACK, WHITE}, prelude::*};
 use std::{mem::swap, ops::{Index, IndexMut}};
 
 #[derive(Clone)]
@@ -149,35 +150,6 @@ impl<T : Clone> IndexMut<IVec3> for Vector3D<T> {
     }
 }
 
-pub fn fill_img_color(color: Color, out_image: &mut Image)
-{
-    for y in 0..out_image.size().y {
-        for x in 0..out_image.size().x {
-            set_img_color(UVec2::new(x, y), color, out_image);
-        }
-    }
-}
-
-pub fn fill_sub_img_color(color: Color, out_image: &mut Image, from: UVec2, to: UVec2)
-{
-    let from = from.clamp(UVec2::ZERO, out_image.size());
-    let to = to.clamp(UVec2::ZERO, out_image.size());
-    for y in from.y..to.y {
-        for x in from.x..to.x {
-            set_img_color(UVec2::new(x, y), color, out_image);
-        }
-    }
-}
-
-pub fn set_img_color(pos: UVec2, color: Color, out_image: &mut Image)
-{
-    let i = (pos.x + pos.y * out_image.size().x) as usize;
-    let color = color.to_linear().to_u8_array();
-    for ch in 0..4 {
-        out_image.data[i*4 + ch] = color[ch];
-    }
-}
-
 pub fn distant_color_no_alpha(color: Color) -> Color
 {
     let vals = color.to_srgba().to_f32_array_no_alpha();
@@ -225,13 +197,13 @@ fn interleave_u32(pos: UVec2) -> u32
 fn block_cipher_tea(mut v0: u32, mut v1: u32) -> UVec2
 {
     let iterations: u32 = 16;
-    let mut sum = 0;
+    let mut sum: u32 = 0;
     const DELTA: u32 = 0x9e3779b9;
     const K: [u32; 4] = [0xa341316c, 0xc8013ea4, 0xad90777d, 0x7e95761e ];
     for _ in 0..iterations {
-        sum += DELTA;
-        v0 += ((v1 << 4) + K[0]) ^ (v1 + sum) ^ ((v1 >> 5) + K[1]);
-        v1 += ((v0 << 4) + K[2]) ^ (v0 + sum) ^ ((v0 >> 5) + K[3]);
+        sum = sum.wrapping_add(DELTA);
+        v0 = v0.wrapping_add((v1 << 4).wrapping_add(K[0]) ^ v1.wrapping_add(sum) ^ (v1 >> 5).wrapping_add(K[1]));
+        v1 = v1.wrapping_add((v0 << 4).wrapping_add(K[2]) ^ v0.wrapping_add(sum) ^ (v0 >> 5).wrapping_add(K[3]));
     }
     return UVec2::new(v0, v1);
 }
@@ -243,7 +215,7 @@ pub fn rand_from_pos_u32(pos: UVec2) -> u32
 
 pub fn rand_from_pos_i8(pos: UVec2) -> i8
 {
-    (rand_from_pos_u32(pos) % 256 - 128) as i8
+    ((rand_from_pos_u32(pos) % 256) as i32 - 128) as i8
 }
 
 // we suppose that there is only one intersection and l_pos1 and l_pos2 are not equal